@@ -0,0 +1,6 @@
+//! Links the `xm_*` functions of `xuanmi_base_support` into a `cdylib` and a `staticlib`,
+//! declared by `include/xuanmi_base_support.h`.
+//! A Rust crate that exports its own `extern "C"` functions can instead depend on
+//! `xuanmi_base_support` with the feature `ffi`, and set its own `crate-type`.
+
+pub use xuanmi_base_support::*;
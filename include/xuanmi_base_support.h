@@ -0,0 +1,68 @@
+#ifndef XUANMI_BASE_SUPPORT_H
+#define XUANMI_BASE_SUPPORT_H
+
+/* Generated by cbindgen from src/ffi.rs. Do not edit; see cbindgen.toml. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Returned by a function of the C interface that succeeds.
+#define XM_OK 0
+
+// Returned by a function of the C interface that fails with an exception
+// whose name has no numeric code. See `exception_names::set_exception_code`.
+#define XM_ERROR -1
+
+// An `Exception` handed to C as an opaque pointer.
+// Obtained from a function of the C interface that fails, and released by `xm_exception_free`.
+typedef struct XmException XmException;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// The name of the exception. Release it by `xm_string_free`. Null if `ex` is null.
+//
+// # Safety
+// `ex` must be null or a live handle.
+char *xm_exception_name(const struct XmException *ex);
+
+// The context of the outermost frame, redacted like any rendered context.
+// Release it by `xm_string_free`. Null if `ex` is null or the frame has no context.
+//
+// # Safety
+// `ex` must be null or a live handle.
+char *xm_exception_context(const struct XmException *ex);
+
+// The whole chain rendered in `exception_format()`, as by `Display`.
+// Release it by `xm_string_free`. Null if `ex` is null.
+//
+// # Safety
+// `ex` must be null or a live handle.
+char *xm_exception_trace(const struct XmException *ex);
+
+// The code returned along with the exception, see `ffi_error_code`. `XM_ERROR` if `ex` is null.
+//
+// # Safety
+// `ex` must be null or a live handle.
+int32_t xm_exception_code(const struct XmException *ex);
+
+// Release an exception. Does nothing if `ex` is null.
+//
+// # Safety
+// `ex` must be null or a live handle, which must not be used afterwards.
+void xm_exception_free(struct XmException *ex);
+
+// Release a string returned by the C interface. Does nothing if `text` is null.
+//
+// # Safety
+// `text` must be null or a string returned by the C interface, which must not be used afterwards.
+void xm_string_free(char *text);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* XUANMI_BASE_SUPPORT_H */
@@ -0,0 +1,144 @@
+use crate::*;
+use regex::Regex;
+
+/// Assert that an `Outcome` is an `Err` whose outermost frame `is_a(name)`, and evaluate to the exception.
+/// On mismatch, the panic message shows the whole chain.
+/// ```ignore
+/// let ex = assert_throws!(read_str_from_file("/nonexistent"), EXN::IOException);
+/// ```
+#[macro_export]
+macro_rules! assert_throws {
+    ($res:expr, $name:expr $(,)?) => {
+        $crate::check_throws($res, $name, stringify!($res))
+    };
+}
+
+/// Assert that an `Outcome` is an `Err` with a frame that `is_a(name)`, and evaluate to the exception.
+#[macro_export]
+macro_rules! assert_chain_contains {
+    ($res:expr, $name:expr $(,)?) => {
+        $crate::check_chain_contains($res, $name, stringify!($res))
+    };
+}
+
+/// Assert that an `Outcome` is an `Err` whose outermost context matches the regular expression `pattern`,
+/// and evaluate to the exception.
+#[macro_export]
+macro_rules! assert_context_matches {
+    ($res:expr, $pattern:expr $(,)?) => {
+        $crate::check_context_matches($res, $pattern, stringify!($res))
+    };
+}
+
+#[track_caller]
+fn unwrap_err<T>(res: Outcome<T>, expr: &str, expected: &str) -> Box<Exception> {
+    match res {
+        Ok(_) => panic!(
+            "`{}` is expected to throw {}, but returns Ok",
+            expr, expected
+        ),
+        Err(ex) => ex,
+    }
+}
+
+#[doc(hidden)]
+#[track_caller]
+pub fn check_throws<T>(res: Outcome<T>, name: &str, expr: &str) -> Box<Exception> {
+    let ex = unwrap_err(res, expr, name);
+    if !ex.is_a(name) {
+        panic!(
+            "`{}` is expected to throw {}, but throws:\n{}",
+            expr,
+            name,
+            ex.render(ExceptionFormat::Classic).trim_end()
+        );
+    }
+    ex
+}
+
+#[doc(hidden)]
+#[track_caller]
+pub fn check_chain_contains<T>(res: Outcome<T>, name: &str, expr: &str) -> Box<Exception> {
+    let ex = unwrap_err(res, expr, &format!("an exception caused by {}", name));
+    let found = ex
+        .chain()
+        .filter_map(|frame| frame.as_exception())
+        .any(|frame| frame.is_a(name));
+    if !found {
+        panic!(
+            "`{}` is expected to throw an exception caused by {}, but throws:\n{}",
+            expr,
+            name,
+            ex.render(ExceptionFormat::Classic).trim_end()
+        );
+    }
+    ex
+}
+
+#[doc(hidden)]
+#[track_caller]
+pub fn check_context_matches<T>(res: Outcome<T>, pattern: &str, expr: &str) -> Box<Exception> {
+    let re = match Regex::new(pattern) {
+        Ok(re) => re,
+        Err(e) => panic!("Invalid pattern `{}`: {}", pattern, e),
+    };
+    let expected = format!("an exception with context matching `{}`", pattern);
+    let ex = unwrap_err(res, expr, &expected);
+    if !re.is_match(ex.get_context().unwrap_or("")) {
+        panic!(
+            "`{}` is expected to throw {}, but throws:\n{}",
+            expr,
+            expected,
+            ex.render(ExceptionFormat::Classic).trim_end()
+        );
+    }
+    ex
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn open() -> Outcome<String> {
+        read_str_from_file("!!$%!$>TXT").catch("LoadConfigException", "Cannot load config v2")
+    }
+
+    fn panic_message(f: impl FnOnce() + std::panic::UnwindSafe) -> String {
+        let payload = std::panic::catch_unwind(f).unwrap_err();
+        payload.downcast_ref::<String>().unwrap().clone()
+    }
+
+    #[test]
+    fn test_assertions() {
+        let ex = assert_throws!(open(), "LoadConfigException");
+        assert_eq!(ex.get_name(), "LoadConfigException");
+        assert_chain_contains!(open(), EXN::IOException);
+        assert_chain_contains!(open(), EXN::UncategorizedException);
+        assert_context_matches!(open(), r"config v\d$");
+
+        let msg = panic_message(|| {
+            assert_throws!(open(), EXN::IOException);
+        });
+        assert!(msg.starts_with("`open()` is expected to throw IOException, but throws:\n"));
+        assert!(msg.contains("Exception \"IOException\""), "{}", msg);
+
+        let msg = panic_message(|| {
+            assert_chain_contains!(open(), EXN::DeserializationException);
+        });
+        assert!(
+            msg.contains("caused by DeserializationException"),
+            "{}",
+            msg
+        );
+
+        let msg = panic_message(|| {
+            assert_context_matches!(open(), "^v2");
+        });
+        assert!(msg.contains("Cannot load config v2"), "{}", msg);
+
+        let msg = panic_message(|| {
+            assert_throws!(Ok::<(), Box<Exception>>(()), EXN::IOException);
+        });
+        assert!(msg.ends_with("but returns Ok"), "{}", msg);
+    }
+}
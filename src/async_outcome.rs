@@ -0,0 +1,244 @@
+#[cfg(not(feature = "std"))]
+use crate::no_std_prelude::*;
+use crate::*;
+use core::future::Future;
+use core::panic::Location;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+/// Future returned by `catch`, `catch_`, `catch_error` and `catch_error_` on a future.
+/// The location is recorded where the combinator is called, not where the future is awaited.
+#[must_use = "futures do nothing unless polled"]
+pub struct CatchFuture<F, E> {
+    fut: F,
+    site: CatchSite,
+    cause: fn(E) -> Cause,
+}
+
+struct CatchSite {
+    name: String,
+    ctx: String,
+    loc: &'static Location<'static>,
+}
+
+impl CatchSite {
+    #[track_caller]
+    fn new(name: &str, ctx: &str) -> Self {
+        CatchSite {
+            name: name.to_string(),
+            ctx: ctx.to_string(),
+            loc: Location::caller(),
+        }
+    }
+
+    fn exception(&self) -> Box<Exception> {
+        let mut ex = Exception::new();
+        ex.set_name(&self.name)
+            .set_file(self.loc.file())
+            .set_line(self.loc.line())
+            .set_column(self.loc.column())
+            .set_context(&self.ctx);
+        ex
+    }
+}
+
+impl<F, E> CatchFuture<F, E> {
+    #[track_caller]
+    fn new(fut: F, name: &str, ctx: &str, cause: fn(E) -> Cause) -> Self {
+        let site = CatchSite::new(name, ctx);
+        CatchFuture { fut, site, cause }
+    }
+}
+
+pub trait TraitFutureResultToOutcome<T, E>: Future<Output = Result<T, E>> + Sized {
+    /// The async counterpart of `TraitStdResultToOutcome::catch`.
+    /// Applied to an `async` block returning `Outcome`,
+    /// it adds a frame with `name` and `ctx` to whatever the block throws.
+    fn catch(self, name: &str, ctx: &str) -> CatchFuture<Self, E>;
+
+    /// equivalent to `catch("", "")`.
+    fn catch_(self) -> CatchFuture<Self, E>;
+}
+
+impl<F, T, E> TraitFutureResultToOutcome<T, E> for F
+where
+    F: Future<Output = Result<T, E>>,
+    E: core::fmt::Display + Send + Sync + 'static,
+{
+    #[track_caller]
+    fn catch(self, name: &str, ctx: &str) -> CatchFuture<Self, E> {
+        CatchFuture::new(self, name, ctx, Cause::new)
+    }
+
+    #[track_caller]
+    fn catch_(self) -> CatchFuture<Self, E> {
+        CatchFuture::new(self, "", "", Cause::new)
+    }
+}
+
+/// The async counterpart of `TraitErrorResultToOutcome`.
+pub trait TraitFutureErrorResultToOutcome<T, E>: Future<Output = Result<T, E>> + Sized {
+    fn catch_error(self, name: &str, ctx: &str) -> CatchFuture<Self, E>;
+    fn catch_error_(self) -> CatchFuture<Self, E>;
+}
+
+impl<F, T, E> TraitFutureErrorResultToOutcome<T, E> for F
+where
+    F: Future<Output = Result<T, E>>,
+    E: core::error::Error + Send + Sync + 'static,
+{
+    #[track_caller]
+    fn catch_error(self, name: &str, ctx: &str) -> CatchFuture<Self, E> {
+        CatchFuture::new(self, name, ctx, Cause::from_error)
+    }
+
+    #[track_caller]
+    fn catch_error_(self) -> CatchFuture<Self, E> {
+        CatchFuture::new(self, "", "", Cause::from_error)
+    }
+}
+
+impl<F, T, E> Future for CatchFuture<F, E>
+where
+    F: Future<Output = Result<T, E>>,
+{
+    type Output = Outcome<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: `fut` is never moved out of `self`, and the other fields are only read.
+        let this = unsafe { self.get_unchecked_mut() };
+        let fut = unsafe { Pin::new_unchecked(&mut this.fut) };
+        match fut.poll(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Ok(v)) => Poll::Ready(Ok(v)),
+            Poll::Ready(Err(e)) => {
+                let mut ex = this.site.exception();
+                ex.set_cause((this.cause)(e));
+                ex.emit_event();
+                Poll::Ready(Err(ex))
+            }
+        }
+    }
+}
+
+/// Future returned by `ifnone` and `ifnone_` on a future.
+/// Like `CatchFuture`, it is located where the combinator is called.
+#[must_use = "futures do nothing unless polled"]
+pub struct IfNoneFuture<F> {
+    fut: F,
+    site: CatchSite,
+}
+
+impl<F> IfNoneFuture<F> {
+    #[track_caller]
+    fn new(fut: F, name: &str, ctx: &str) -> Self {
+        let site = CatchSite::new(name, ctx);
+        IfNoneFuture { fut, site }
+    }
+}
+
+pub trait TraitFutureOptionToOutcome<T>: Future<Output = Option<T>> + Sized {
+    /// The async counterpart of `TraitStdOptionToOutcome::ifnone`.
+    fn ifnone(self, name: &str, ctx: &str) -> IfNoneFuture<Self>;
+    fn ifnone_(self) -> IfNoneFuture<Self>;
+}
+
+impl<F, T> TraitFutureOptionToOutcome<T> for F
+where
+    F: Future<Output = Option<T>>,
+{
+    #[track_caller]
+    fn ifnone(self, name: &str, ctx: &str) -> IfNoneFuture<Self> {
+        IfNoneFuture::new(self, name, ctx)
+    }
+
+    #[track_caller]
+    fn ifnone_(self) -> IfNoneFuture<Self> {
+        IfNoneFuture::new(self, "", "")
+    }
+}
+
+impl<F, T> Future for IfNoneFuture<F>
+where
+    F: Future<Output = Option<T>>,
+{
+    type Output = Outcome<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: `fut` is never moved out of `self`, and `site` is only read.
+        let this = unsafe { self.get_unchecked_mut() };
+        let fut = unsafe { Pin::new_unchecked(&mut this.fut) };
+        match fut.poll(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Some(v)) => Poll::Ready(Ok(v)),
+            Poll::Ready(None) => {
+                let mut ex = this.site.exception();
+                ex.capture_backtrace_if_enabled();
+                ex.emit_event();
+                Poll::Ready(Err(ex))
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::*;
+    use futures::executor::block_on;
+
+    async fn open_missing() -> std::io::Result<std::fs::File> {
+        std::fs::File::open("!!$%!$>TXT")
+    }
+
+    async fn find_nothing() -> Option<u32> {
+        None
+    }
+
+    #[test]
+    fn test_future_catch() {
+        let fut = open_missing().catch(EXN::IOException, "async open");
+        let line = line!() - 1;
+        let ex = block_on(fut).unwrap_err();
+        assert_eq!(ex.get_name(), EXN::IOException);
+        assert!(ex
+            .to_string()
+            .contains(&format!("src/async_outcome.rs:{}:", line)));
+        assert!(ex.root_cause().is::<std::io::Error>());
+
+        let ex = block_on(find_nothing().ifnone_()).unwrap_err();
+        assert_eq!(ex.chain().count(), 1);
+    }
+
+    #[test]
+    fn test_future_display_errors() {
+        let fut = async { Err::<(), String>("no route".to_string()) };
+        let ex = block_on(fut.catch(EXN::HttpPostException, "")).unwrap_err();
+        assert_eq!(
+            ex.root_cause().downcast_ref::<String>().unwrap(),
+            "no route"
+        );
+
+        let fut = async { Ok::<_, anyhow::Error>(open_missing().await?) };
+        let ex = block_on(fut.catch_()).unwrap_err();
+        let err = anyhow::Error::from(ex);
+        assert!(err.root_cause().is::<std::io::Error>());
+    }
+
+    #[test]
+    fn test_async_block_catch() {
+        let job = async {
+            let _f = open_missing().catch_().await?;
+            let n = find_nothing()
+                .ifnone(EXN::IndexOutOfBoundException, "")
+                .await?;
+            Ok::<_, Box<Exception>>(n)
+        };
+        let ex = block_on(job.catch("JobException", "whole block")).unwrap_err();
+        let names: Vec<_> = ex
+            .chain()
+            .filter_map(|frame| frame.as_exception())
+            .map(|ex| ex.get_name())
+            .collect();
+        assert_eq!(names, ["JobException", ""]);
+    }
+}
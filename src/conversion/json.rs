@@ -35,6 +35,23 @@ where
     Ok(json)
 }
 
+/// Convert an object to formatted json string meant for logs,
+/// with the secrets redacted by the policy of `set_redaction_policy`.
+pub fn obj_to_json_pretty_redacted<T>(obj: &T) -> Outcome<String>
+where
+    T: Serialize,
+{
+    let mut val: JsonValue = serde_json::to_value(obj).catch(
+        EXN::SerializationException,
+        &format!(
+            "Failed to convert object of type `{}` to serde_json::Value",
+            std::any::type_name::<T>()
+        ),
+    )?;
+    redact_json(&mut val);
+    obj_to_json_pretty(&val)
+}
+
 /// Convert a json string to an object.
 pub fn json_to_obj<'a, T>(text: &'a str) -> Outcome<T>
 where
@@ -83,7 +100,7 @@ impl JsonDictGet for JsonDict {
         match self.get(field) {
             Some(jval) => {
                 let val: V = jval_to_obj(jval.clone()).catch(
-                    "JsonInvalidFieldException",
+                    EXN::JsonInvalidFieldException,
                     &format!(
                         "The provided JSON field \"{}\" cannot be parsed into type `{}`",
                         field,
@@ -94,7 +111,7 @@ impl JsonDictGet for JsonDict {
             }
             None => {
                 throw!(
-                    "JsonNoRequiredFieldException",
+                    EXN::JsonNoRequiredFieldException,
                     &format!("The required JSON field \"{}\" is absent", field)
                 );
             }
@@ -108,7 +125,7 @@ impl JsonDictGet for JsonDict {
         match self.get(field) {
             Some(jval) => {
                 let val: V = jval_to_obj(jval.clone()).catch(
-                    "JsonInvalidFieldException",
+                    EXN::JsonInvalidFieldException,
                     &format!(
                         "The provided JSON field \"{}\" cannot be parsed into type `{}`",
                         field,
@@ -124,7 +141,7 @@ impl JsonDictGet for JsonDict {
     fn jcatch_(&self) -> Outcome<JsonValue> {
         let status: String = self.get_must_provide("status")
             .catch(
-                "DataFormatException", 
+                EXN::DataFormatException,
                 "If call `catch(...)` or `catch_()` on a JsonDict object, the `status` field must exist."
             )?;
         if status == "ok" {
@@ -138,7 +155,7 @@ impl JsonDictGet for JsonDict {
                         ret = self.get(special_key).unwrap().clone();
                     } else {
                         throw!(
-                            "DataFormatException",
+                            EXN::DataFormatException,
                             &format!(
                                 r#"If call `catch(...)` or `catch_()` on a JsonDict object,
                                 the object should have at most 1 field within the following list:
@@ -158,11 +175,16 @@ impl JsonDictGet for JsonDict {
             }
             return Ok(ret);
         } else if status == "err" {
-            let trace: String = self.get_must_provide("trace").catch_()?;
-            return Err(trace).catch_()?;
+            // `trace` is either a structured `Exception`, or its flattened string from older peers.
+            let trace: JsonValue = self.get_must_provide("trace").catch_()?;
+            let remote: Box<Exception> = match trace {
+                JsonValue::String(trace) => return Err(trace).catch_(),
+                trace => jval_to_obj(trace).catch_()?,
+            };
+            return Err(remote).catch_()?;
         } else {
             throw!(
-                "DataFormatException",
+                EXN::DataFormatException,
                 r#"If call `catch(...)` or `catch_()` on a JsonDict object,
                 the `status` field must be either "ok" or "err"."#
             );
@@ -181,3 +203,31 @@ impl<T: AsRef<str>> StringToJsonDict for T {
         Ok(jd)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn test_jcatch_remote_exception() -> Outcome<()> {
+        fn remote() -> Outcome<()> {
+            throw!(EXN::IOException, "Disk is full");
+        }
+        let remote_ex = remote().unwrap_err();
+        let text = format!(r#"{{"status":"err","trace":{}}}"#, obj_to_json(&remote_ex)?);
+        let ex = text.try_into_json_dict()?.jcatch_().unwrap_err();
+        let frames: Vec<Frame> = ex.chain().collect();
+        assert_eq!(frames.len(), 2);
+        let rebuilt = frames[1].as_exception().ifnone_()?;
+        assert_eq!(rebuilt.get_name(), EXN::IOException);
+        assert_eq!(rebuilt.get_context(), Some("Disk is full"));
+
+        let text = r#"{"status":"err","trace":"flattened trace"}"#;
+        let ex = text.try_into_json_dict()?.jcatch_().unwrap_err();
+        assert_eq!(
+            ex.root_cause().downcast_ref::<String>().ifnone_()?,
+            "flattened trace"
+        );
+        Ok(())
+    }
+}
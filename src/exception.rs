@@ -1,4 +1,20 @@
-use std::{fmt, result::Result as StdResult};
+#[cfg(feature = "tracing")]
+use crate::logging::{self, SpanInfo};
+#[cfg(not(feature = "std"))]
+use crate::no_std_prelude::*;
+#[cfg(feature = "std")]
+use crate::ExceptionMetadata;
+use core::{any::Any, fmt, result::Result as StdResult};
+use serde::{
+    de::{MapAccess, Visitor},
+    ser::SerializeStruct,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+#[cfg(feature = "std")]
+use std::{
+    backtrace::Backtrace,
+    sync::atomic::{AtomicU8, Ordering},
+};
 
 pub struct Exception {
     name: String,
@@ -6,25 +22,123 @@ pub struct Exception {
     line: u32,
     column: u32,
     context: Option<String>,
-    inner: Option<Box<dyn std::string::ToString + Send + Sync>>,
+    fields: Fields,
+    #[cfg(feature = "tracing")]
+    spans: Vec<SpanInfo>,
+    inner: Option<Cause>,
+    #[cfg(feature = "std")]
+    backtrace: Option<Backtrace>,
+    #[cfg(feature = "std")]
+    metadata: Option<ExceptionMetadata>,
 }
 
-unsafe impl Send for Exception {}
-unsafe impl Sync for Exception {}
+/// Key/value pairs attached to an exception frame, in insertion order.
+pub type Fields = Vec<(String, serde_json::Value)>;
+
+/// Convert a value to a field value of an exception.
+/// A value that cannot be represented in JSON becomes `null`.
+pub fn field_value<V: Serialize + ?Sized>(value: &V) -> serde_json::Value {
+    serde_json::to_value(value).unwrap_or(serde_json::Value::Null)
+}
+
+/// Build `Fields` from `key = value` pairs, e.g. `fields![path = p, len = buf.len()]`.
+/// Every value must implement `serde::Serialize`.
+#[macro_export]
+macro_rules! fields {
+    ($($key:ident = $val:expr),* $(,)?) => {
+        $crate::__private::vec![$((
+            $crate::__private::ToString::to_string(stringify!($key)),
+            $crate::field_value(&$val),
+        )),*]
+    };
+}
 
 /// Make every fail-able function return StdResult<T, Box<dyn StdError>>.
 pub type Outcome<T> = StdResult<T, Box<Exception>>;
 
+/// A switch that defaults to whether an env-var is set to a value other than "0",
+/// read on first use, and can be overridden at runtime.
+#[cfg(feature = "std")]
+pub(crate) struct EnvToggle {
+    /// 0: not yet read from the environment; 1: disabled; 2: enabled.
+    mode: AtomicU8,
+    var: &'static str,
+}
+
+#[cfg(feature = "std")]
+impl EnvToggle {
+    pub(crate) const fn new(var: &'static str) -> Self {
+        EnvToggle {
+            mode: AtomicU8::new(0),
+            var,
+        }
+    }
+
+    pub(crate) fn get(&self) -> bool {
+        match self.mode.load(Ordering::Relaxed) {
+            1 => false,
+            2 => true,
+            _ => {
+                let enabled = match std::env::var(self.var) {
+                    Ok(val) => val != "0",
+                    Err(_) => false,
+                };
+                self.set(enabled);
+                enabled
+            }
+        }
+    }
+
+    pub(crate) fn set(&self, enabled: bool) {
+        self.mode
+            .store(if enabled { 2 } else { 1 }, Ordering::Relaxed);
+    }
+}
+
+#[cfg(feature = "std")]
+static BACKTRACE: EnvToggle = EnvToggle::new("XUANMI_BACKTRACE");
+
+/// Whether `capture_backtrace_if_enabled` captures a backtrace.
+/// Unless overridden by `set_backtrace_enabled`,
+/// it is enabled iff the env-var `XUANMI_BACKTRACE` is set to a value other than "0".
+#[cfg(feature = "std")]
+pub fn backtrace_enabled() -> bool {
+    BACKTRACE.get()
+}
+
+#[cfg(feature = "std")]
+pub fn set_backtrace_enabled(enabled: bool) {
+    BACKTRACE.set(enabled);
+}
+
 impl Exception {
+    /// No backtrace is captured here, see `capture_backtrace_if_enabled`.
     pub fn new() -> Box<Self> {
-        Box::new(Exception {
+        #[cfg_attr(not(feature = "std"), allow(unused_mut))]
+        let mut ex = Box::new(Exception {
             name: "UnknownException".to_string(),
             file: String::new(),
             line: 0,
             column: 0,
             context: None,
+            fields: Fields::new(),
+            #[cfg(feature = "tracing")]
+            spans: Vec::new(),
             inner: None,
-        })
+            #[cfg(feature = "std")]
+            backtrace: None,
+            #[cfg(feature = "std")]
+            metadata: None,
+        });
+        #[cfg(feature = "std")]
+        if crate::metadata_capture_enabled() {
+            ex.capture_metadata();
+        }
+        #[cfg(feature = "tracing")]
+        if logging::span_capture_enabled() {
+            ex.spans = logging::current_spans();
+        }
+        ex
     }
 
     pub fn dummy() -> Box<Self> {
@@ -34,7 +148,14 @@ impl Exception {
             line: 0,
             column: 0,
             context: None,
+            fields: Fields::new(),
+            #[cfg(feature = "tracing")]
+            spans: Vec::new(),
             inner: None,
+            #[cfg(feature = "std")]
+            backtrace: None,
+            #[cfg(feature = "std")]
+            metadata: None,
         })
     }
 
@@ -68,12 +189,86 @@ impl Exception {
         self
     }
 
+    /// If `err` is an `Exception`, the backtrace of `self` is dropped,
+    /// since only the innermost frame of a chain keeps one.
+    #[inline]
+    pub fn set_caused_by(&mut self, err: impl fmt::Display + Send + Sync + 'static) -> &mut Self {
+        self.set_cause(Cause::new(err))
+    }
+
+    /// Like `set_caused_by`, but `err` is also returned by `Error::source`,
+    /// so that it is still reachable once the exception is converted into e.g. `anyhow::Error`.
     #[inline]
-    pub fn set_caused_by(
+    pub fn set_caused_by_error(
         &mut self,
-        err: impl std::string::ToString + Send + Sync + 'static,
+        err: impl core::error::Error + Send + Sync + 'static,
     ) -> &mut Self {
-        self.inner = Some(Box::new(err));
+        self.set_cause(Cause::from_error(err))
+    }
+
+    pub(crate) fn set_cause(&mut self, cause: Cause) -> &mut Self {
+        #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+        if let Some(inner) = cause.as_frame().as_exception() {
+            #[cfg(feature = "std")]
+            {
+                self.backtrace = None;
+                self.metadata = None;
+            }
+            // Spans are shown only where they change along the chain.
+            #[cfg(feature = "tracing")]
+            if inner.spans == self.spans {
+                self.spans.clear();
+            }
+        } else {
+            self.capture_backtrace_if_enabled();
+        }
+        self.inner = Some(cause);
+        self
+    }
+
+    /// Capture a backtrace regardless of `backtrace_enabled()`.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn capture_backtrace(&mut self) -> &mut Self {
+        self.backtrace = Some(Backtrace::force_capture());
+        self
+    }
+
+    /// Capture a backtrace if `backtrace_enabled()` and this frame has none yet.
+    /// Called for the frame an exception originates from: by `throw!` and its siblings,
+    /// and by `set_caused_by` with an error that is not an `Exception`.
+    /// Frames that wrap an `Exception` do not capture, since only the innermost backtrace is kept.
+    #[inline]
+    pub fn capture_backtrace_if_enabled(&mut self) -> &mut Self {
+        #[cfg(feature = "std")]
+        if self.backtrace.is_none() && backtrace_enabled() {
+            self.capture_backtrace();
+        }
+        self
+    }
+
+    /// Record an `ExceptionMetadata` regardless of `metadata_capture_enabled()`.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn capture_metadata(&mut self) -> &mut Self {
+        self.metadata = Some(ExceptionMetadata::capture());
+        self
+    }
+
+    /// Attach a key/value pair to this frame, replacing any previous value of `key`.
+    pub fn set_field<V: Serialize + ?Sized>(&mut self, key: &str, value: &V) -> &mut Self {
+        let value = field_value(value);
+        match self.fields.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value,
+            None => self.fields.push((key.to_string(), value)),
+        }
+        self
+    }
+
+    pub fn set_fields(&mut self, fields: Fields) -> &mut Self {
+        for (key, value) in fields {
+            self.set_field(&key, &value);
+        }
         self
     }
 
@@ -82,6 +277,21 @@ impl Exception {
         &self.name
     }
 
+    #[inline]
+    pub fn get_file(&self) -> &str {
+        &self.file
+    }
+
+    #[inline]
+    pub fn get_line(&self) -> u32 {
+        self.line
+    }
+
+    #[inline]
+    pub fn get_column(&self) -> u32 {
+        self.column
+    }
+
     #[inline]
     pub fn get_context(&self) -> Option<&str> {
         match &self.context {
@@ -89,35 +299,410 @@ impl Exception {
             None => None,
         }
     }
+
+    /// The numeric code registered for the name of this frame, if any.
+    /// See `exception_names::set_exception_code`.
+    #[inline]
+    pub fn get_code(&self) -> Option<u32> {
+        crate::exception_names::code_of(&self.name)
+    }
+
+    /// The HTTP status registered for the name of this frame, or for its closest category.
+    #[inline]
+    pub fn get_http_status(&self) -> u16 {
+        crate::exception_names::http_status_of(&self.name)
+    }
+
+    /// Whether the name of this frame is `category`, or is registered under `category`.
+    /// See `exception_names::register_exception_name`.
+    #[inline]
+    pub fn is_a(&self, category: &str) -> bool {
+        crate::exception_names::is_subclass_of(&self.name, category)
+    }
+
+    /// The backtrace kept by the innermost frame of the chain, if any.
+    #[cfg(feature = "std")]
+    pub fn get_backtrace(&self) -> Option<&Backtrace> {
+        self.chain()
+            .filter_map(|frame| frame.as_exception())
+            .find_map(|ex| ex.backtrace.as_ref())
+    }
+
+    /// The metadata of the innermost frame that has one.
+    /// Recorded if `metadata_capture_enabled()` when that frame was created.
+    #[cfg(feature = "std")]
+    pub fn get_metadata(&self) -> Option<&ExceptionMetadata> {
+        self.chain()
+            .filter_map(|frame| frame.as_exception())
+            .find_map(|ex| ex.metadata.as_ref())
+    }
+
+    #[cfg(feature = "std")]
+    #[inline]
+    pub(crate) fn get_own_metadata(&self) -> Option<&ExceptionMetadata> {
+        self.metadata.as_ref()
+    }
+
+    #[inline]
+    pub fn get_fields(&self) -> &Fields {
+        &self.fields
+    }
+
+    #[inline]
+    pub fn get_field(&self, key: &str) -> Option<&serde_json::Value> {
+        self.fields.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    /// The spans that were active when this frame was created, outermost first.
+    /// See `logging::set_span_capture_enabled`.
+    #[cfg(feature = "tracing")]
+    #[inline]
+    pub fn get_spans(&self) -> &[SpanInfo] {
+        &self.spans
+    }
+
+    /// Emit a `tracing` event for this frame, if enabled by `logging::set_exception_event_level`.
+    /// The constructors and macros of this module call it once a frame is complete.
+    /// Does nothing without the `tracing` feature.
+    #[inline]
+    pub fn emit_event(&self) {
+        #[cfg(feature = "tracing")]
+        logging::emit_exception_event(self);
+    }
+
+    #[cfg(feature = "std")]
+    #[inline]
+    pub(crate) fn get_own_backtrace(&self) -> Option<&Backtrace> {
+        self.backtrace.as_ref()
+    }
+
+    /// The frame directly below this one, if any.
+    #[inline]
+    pub fn get_caused_by(&self) -> Option<Frame<'_>> {
+        self.inner.as_ref().map(|cause| cause.as_frame())
+    }
+
+    /// Iterate over the frames of the exception chain, starting from `self`.
+    /// Every frame but the last one is an `Exception`;
+    /// the last one is either an `Exception` or the foreign error it was caught from.
+    pub fn chain(&self) -> Chain<'_> {
+        Chain {
+            next: Some(Frame::of(self)),
+        }
+    }
+
+    /// The innermost frame of the exception chain.
+    pub fn root_cause(&self) -> Frame<'_> {
+        self.chain().last().unwrap_or(Frame::of(self))
+    }
+
+    /// A hash of the name, file and line of every frame, including the children of a `MultiException`.
+    /// Contexts, fields and foreign errors are left out, so the same failure
+    /// at the same place always has the same fingerprint, across runs and builds.
+    /// Shown as `{:016x}` in `tracing` events.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hash = Fnv1a::new();
+        hash_frames(self, &mut hash);
+        hash.0
+    }
+}
+
+/// 64-bit FNV-1a. Unlike `DefaultHasher`, its output is specified, thus stable across Rust versions.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+fn hash_frames(ex: &Exception, hash: &mut Fnv1a) {
+    for frame in ex.chain() {
+        if let Some(ex) = frame.as_exception() {
+            hash.write(ex.get_name().as_bytes());
+            hash.write(&[0xff]);
+            hash.write(ex.get_file().as_bytes());
+            hash.write(&[0xff]);
+            hash.write(&ex.get_line().to_le_bytes());
+        } else if let Some(multi) = frame.downcast_ref::<crate::MultiException>() {
+            hash.write(b"[");
+            for child in multi.get_children() {
+                hash_frames(child, hash);
+                hash.write(b",");
+            }
+            hash.write(b"]");
+        }
+    }
+}
+
+type FmtFn = fn(&(dyn Any + Send + Sync), &mut fmt::Formatter<'_>) -> fmt::Result;
+
+fn fmt_as<E: fmt::Display + 'static>(
+    obj: &(dyn Any + Send + Sync),
+    f: &mut fmt::Formatter<'_>,
+) -> fmt::Result {
+    match obj.downcast_ref::<E>() {
+        Some(e) => fmt::Display::fmt(e, f),
+        None => Ok(()),
+    }
+}
+
+type SourceFn = fn(&(dyn Any + Send + Sync)) -> Option<&(dyn core::error::Error + 'static)>;
+
+fn source_as<E: core::error::Error + 'static>(
+    obj: &(dyn Any + Send + Sync),
+) -> Option<&(dyn core::error::Error + 'static)> {
+    match obj.downcast_ref::<E>() {
+        Some(e) => Some(e),
+        None => None,
+    }
+}
+
+fn source_boxed(obj: &(dyn Any + Send + Sync)) -> Option<&(dyn core::error::Error + 'static)> {
+    match obj.downcast_ref::<Box<dyn core::error::Error + Send + Sync>>() {
+        Some(err) => Some(err.as_ref()),
+        None => None,
+    }
+}
+
+#[cfg(feature = "std")]
+fn source_anyhow_root(
+    obj: &(dyn Any + Send + Sync),
+) -> Option<&(dyn core::error::Error + 'static)> {
+    Some(obj.downcast_ref::<anyhow::Error>()?.root_cause())
+}
+
+#[cfg(feature = "std")]
+fn fmt_anyhow_root(obj: &(dyn Any + Send + Sync), f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match obj.downcast_ref::<anyhow::Error>() {
+        Some(err) => fmt::Display::fmt(err.root_cause(), f),
+        None => Ok(()),
+    }
+}
+
+/// The error an `Exception` is caused by.
+/// The error object is kept as-is, so that it can be downcast later.
+pub struct Cause {
+    obj: Box<dyn Any + Send + Sync>,
+    fmt: FmtFn,
+    source: Option<SourceFn>,
+}
+
+impl Cause {
+    pub fn new<E>(err: E) -> Self
+    where
+        E: fmt::Display + Send + Sync + 'static,
+    {
+        let obj: Box<dyn Any + Send + Sync> = Box::new(err);
+        // `Outcome` carries `Box<Exception>`. Unbox it,
+        // so that every nested frame can be downcast to `Exception`.
+        let obj = match obj.downcast::<Box<Exception>>() {
+            Ok(ex) => {
+                let obj: Box<dyn Any + Send + Sync> = *ex;
+                return Cause {
+                    obj,
+                    fmt: fmt_as::<Exception>,
+                    source: Some(source_as::<Exception>),
+                };
+            }
+            Err(obj) => obj,
+        };
+        if obj.is::<Exception>() {
+            return Cause {
+                obj,
+                fmt: fmt_as::<Exception>,
+                source: Some(source_as::<Exception>),
+            };
+        }
+        let obj = match obj.downcast::<Box<dyn core::error::Error + Send + Sync>>() {
+            Ok(err) => return Cause::from_boxed_error(*err),
+            Err(obj) => obj,
+        };
+        #[cfg(feature = "std")]
+        let obj = match obj.downcast::<anyhow::Error>() {
+            Ok(err) => return Cause::from_anyhow(*err),
+            Err(obj) => obj,
+        };
+        Cause {
+            obj,
+            fmt: fmt_as::<E>,
+            source: None,
+        }
+    }
+
+    /// Like `new`, but `err` is also what `as_error` returns.
+    pub fn from_error<E>(err: E) -> Self
+    where
+        E: core::error::Error + Send + Sync + 'static,
+    {
+        let mut cause = Cause::new(err);
+        if cause.source.is_none() {
+            cause.source = Some(source_as::<E>);
+        }
+        cause
+    }
+
+    /// Like `from_error`, for a boxed error. `new` also takes this path for a boxed error.
+    /// A boxed `Exception` is taken back with all its frames.
+    pub fn from_boxed_error(err: Box<dyn core::error::Error + Send + Sync>) -> Self {
+        let err = match err.downcast::<Exception>() {
+            Ok(ex) => return Cause::new(ex),
+            Err(err) => err,
+        };
+        let err = match err.downcast::<Box<Exception>>() {
+            Ok(ex) => return Cause::new(*ex),
+            Err(err) => err,
+        };
+        Cause {
+            obj: Box::new(err),
+            fmt: fmt_as::<Box<dyn core::error::Error + Send + Sync>>,
+            source: Some(source_boxed),
+        }
+    }
+
+    /// Every context layer of `err` becomes an `Exception` frame.
+    /// If the underlying error is an `Exception`, it is taken back with all its frames.
+    /// Otherwise the bottom frame is `err` itself, displayed as its root cause,
+    /// so that the original error is still reachable by `anyhow::Error::downcast_ref`.
+    #[cfg(feature = "std")]
+    fn from_anyhow(err: anyhow::Error) -> Self {
+        let mut layers: Vec<String> = Vec::new();
+        let mut reaches_exception = false;
+        for layer in err.chain() {
+            if layer.is::<Exception>() || layer.is::<Box<Exception>>() {
+                reaches_exception = true;
+                break;
+            }
+            layers.push(layer.to_string());
+        }
+        let underlying = match err.downcast::<Exception>() {
+            Ok(ex) => Ok(Box::new(ex)),
+            Err(err) => err.downcast::<Box<Exception>>(),
+        };
+        let mut cause = match underlying {
+            Ok(ex) => Cause::new(ex),
+            Err(err) => {
+                if !reaches_exception {
+                    // displayed by the bottom frame.
+                    layers.pop();
+                }
+                Cause {
+                    obj: Box::new(err),
+                    fmt: fmt_anyhow_root,
+                    source: Some(source_anyhow_root),
+                }
+            }
+        };
+        for ctx in layers.into_iter().rev() {
+            let mut ex = Exception::new();
+            ex.set_name(crate::EXN::UncategorizedException)
+                .set_context(&ctx)
+                .set_cause(cause);
+            cause = Cause::new(ex);
+        }
+        cause
+    }
+
+    /// The error as returned by `Error::source` of the `Exception` it causes:
+    /// a nested `Exception`, a foreign error given to `from_error`,
+    /// or the root cause of an `anyhow::Error`. `None` for any other cause.
+    #[inline]
+    pub fn as_error(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        (self.source?)(self.obj.as_ref())
+    }
+
+    #[inline]
+    pub fn as_frame(&self) -> Frame<'_> {
+        Frame {
+            obj: self.obj.as_ref(),
+            fmt: self.fmt,
+        }
+    }
+}
+
+impl fmt::Display for Cause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (self.fmt)(self.obj.as_ref(), f)
+    }
+}
+
+/// A borrowed frame of an exception chain.
+#[derive(Clone, Copy)]
+pub struct Frame<'a> {
+    obj: &'a (dyn Any + Send + Sync),
+    fmt: FmtFn,
+}
+
+impl<'a> Frame<'a> {
+    #[inline]
+    fn of(ex: &'a Exception) -> Self {
+        Frame {
+            obj: ex,
+            fmt: fmt_as::<Exception>,
+        }
+    }
+
+    #[inline]
+    pub fn is<T: Any>(&self) -> bool {
+        self.obj.is::<T>()
+    }
+
+    #[inline]
+    pub fn downcast_ref<T: Any>(&self) -> Option<&'a T> {
+        self.obj.downcast_ref::<T>()
+    }
+
+    #[inline]
+    pub fn as_exception(&self) -> Option<&'a Exception> {
+        self.downcast_ref::<Exception>()
+    }
+}
+
+impl fmt::Display for Frame<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (self.fmt)(self.obj, f)
+    }
+}
+
+impl fmt::Debug for Frame<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (self.fmt)(self.obj, f)
+    }
+}
+
+/// Iterator returned by `Exception::chain`.
+pub struct Chain<'a> {
+    next: Option<Frame<'a>>,
+}
+
+impl<'a> Iterator for Chain<'a> {
+    type Item = Frame<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let frame = self.next.take()?;
+        self.next = frame.as_exception().and_then(|ex| ex.get_caused_by());
+        Some(frame)
+    }
 }
 
 /// std::string::ToString has a default to_string() implementation
 /// for any type satisfying std::fmt::Display, and the type needn't be Sized.
 impl fmt::Display for Exception {
+    /// Renders `exception_format()`, or `ExceptionFormat::Compact` for `{:#}`.
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut msg: String = format!("Exception \"{}\" occurs at \"{}", self.name, self.file);
-        if self.line > 0 {
-            msg += &format!(":{}", &self.line);
-        }
-        if self.column > 0 {
-            msg += &format!(":{}", &self.column);
-        }
-        msg += "\"";
-        if self.context.is_some() {
-            let ctx = self.context.as_ref().unwrap().trim();
-            if ctx != "" {
-                msg += &format!("\nContext: {}", ctx);
-            }
-        }
-        if self.inner.is_some() {
-            // if self.inner is Some(Exception)
-            msg += &format!(
-                "\nCaused by:\n{}",
-                &self.inner.as_ref().unwrap().to_string()
-            );
-        }
-        msg += "\n";
-        write!(f, "{}", msg)
+        let format = if f.alternate() {
+            crate::ExceptionFormat::Compact
+        } else {
+            crate::exception_format()
+        };
+        write!(f, "{}", self.render(format))
     }
 }
 
@@ -127,7 +712,204 @@ impl fmt::Debug for Exception {
     }
 }
 
-impl std::error::Error for Exception {}
+impl core::error::Error for Exception {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        self.inner.as_ref()?.as_error()
+    }
+}
+
+/// Every frame is serialized as a JSON object.
+/// The foreign error at the bottom of the chain, if any, is serialized as its `Display` string,
+/// except for a `MultiException`, which is serialized as an array of its children.
+impl Serialize for Exception {
+    fn serialize<S: Serializer>(&self, serializer: S) -> StdResult<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        #[serde(untagged)]
+        enum CauseRef<'a> {
+            Exception(&'a Exception),
+            Multiple(&'a [Exception]),
+            Error(String),
+        }
+
+        /// A JSON object whose entries keep the order of `fields`.
+        struct FieldsRef<'a>(&'a Fields);
+
+        impl Serialize for FieldsRef<'_> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> StdResult<S::Ok, S::Error> {
+                serializer.collect_map(self.0.iter().map(|(k, v)| (k, v)))
+            }
+        }
+
+        let caused_by = self.get_caused_by().map(|frame| {
+            if let Some(ex) = frame.as_exception() {
+                CauseRef::Exception(ex)
+            } else if let Some(multi) = frame.downcast_ref::<crate::MultiException>() {
+                CauseRef::Multiple(multi.get_children())
+            } else {
+                CauseRef::Error(frame.to_string())
+            }
+        });
+        #[cfg(feature = "std")]
+        let len = if self.metadata.is_some() { 9 } else { 8 };
+        #[cfg(not(feature = "std"))]
+        let len = 8;
+        let mut state = serializer.serialize_struct("Exception", len)?;
+        state.serialize_field("name", &self.name)?;
+        state.serialize_field("code", &self.get_code())?;
+        state.serialize_field("file", &self.file)?;
+        state.serialize_field("line", &self.line)?;
+        state.serialize_field("column", &self.column)?;
+        state.serialize_field("context", &self.context)?;
+        state.serialize_field("fields", &FieldsRef(&self.fields))?;
+        state.serialize_field("caused_by", &caused_by)?;
+        #[cfg(feature = "std")]
+        match &self.metadata {
+            Some(metadata) => state.serialize_field("metadata", metadata)?,
+            None => state.skip_field("metadata")?,
+        }
+        state.end()
+    }
+}
+
+/// A foreign error is rebuilt as a `String`, hence can no longer be downcast to its original type.
+/// A `MultiException` is rebuilt with all its children.
+impl<'de> Deserialize<'de> for Exception {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> StdResult<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum CauseOwned {
+            Exception(Box<Exception>),
+            Multiple(Vec<Exception>),
+            Error(String),
+        }
+
+        /// Reads the entries of `fields` in the order they are written.
+        fn deserialize_fields<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> StdResult<Fields, D::Error> {
+            struct FieldsVisitor;
+
+            impl<'de> Visitor<'de> for FieldsVisitor {
+                type Value = Fields;
+
+                fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    f.write_str("a map of fields")
+                }
+
+                fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> StdResult<Fields, A::Error> {
+                    let mut fields = Fields::new();
+                    while let Some((key, value)) = map.next_entry::<String, serde_json::Value>()? {
+                        match fields.iter_mut().find(|(k, _)| *k == key) {
+                            Some((_, v)) => *v = value,
+                            None => fields.push((key, value)),
+                        }
+                    }
+                    Ok(fields)
+                }
+            }
+
+            deserializer.deserialize_map(FieldsVisitor)
+        }
+
+        #[derive(Deserialize)]
+        struct ExceptionOwned {
+            name: String,
+            #[serde(default)]
+            file: String,
+            #[serde(default)]
+            line: u32,
+            #[serde(default)]
+            column: u32,
+            #[serde(default)]
+            context: Option<String>,
+            #[serde(default, deserialize_with = "deserialize_fields")]
+            fields: Fields,
+            #[serde(default)]
+            caused_by: Option<CauseOwned>,
+            #[cfg(feature = "std")]
+            #[serde(default)]
+            metadata: Option<ExceptionMetadata>,
+        }
+
+        let repr = ExceptionOwned::deserialize(deserializer)?;
+        Ok(Exception {
+            name: repr.name,
+            file: repr.file,
+            line: repr.line,
+            column: repr.column,
+            context: repr.context,
+            fields: repr.fields,
+            #[cfg(feature = "tracing")]
+            spans: Vec::new(),
+            inner: repr.caused_by.map(|cause| match cause {
+                CauseOwned::Exception(ex) => Cause::new(ex),
+                CauseOwned::Multiple(children) => Cause::new(crate::MultiException::from(children)),
+                CauseOwned::Error(err) => Cause::new(err),
+            }),
+            #[cfg(feature = "std")]
+            backtrace: None,
+            #[cfg(feature = "std")]
+            metadata: repr.metadata,
+        })
+    }
+}
+
+/// Lets `?` turn an `anyhow::Error` into an `Outcome`,
+/// equivalent to `.catch_()` on the `anyhow::Result`.
+/// The other direction needs no extra code:
+/// `Box<Exception>` implements `std::error::Error`, so `?` in an `anyhow::Result` function just works.
+#[cfg(feature = "std")]
+impl From<anyhow::Error> for Box<Exception> {
+    #[track_caller]
+    fn from(err: anyhow::Error) -> Self {
+        let mut ex = Exception::new();
+        let loc = ::core::panic::Location::caller();
+        ex.set_name("")
+            .set_file(loc.file())
+            .set_line(loc.line())
+            .set_column(loc.column())
+            .set_context("")
+            .set_caused_by(err);
+        ex.emit_event();
+        ex
+    }
+}
+
+/// Lets `?` turn the common error types into an `Outcome`,
+/// equivalent to `.catch(name, "")` with the `EXN` name listed for the type.
+macro_rules! impl_from_error {
+    ($($err:ty => $name:expr),+ $(,)?) => {
+        $(
+            impl From<$err> for Box<Exception> {
+                #[track_caller]
+                fn from(err: $err) -> Self {
+                    let mut ex = Exception::new();
+                    let loc = ::core::panic::Location::caller();
+                    ex.set_name($name)
+                        .set_file(loc.file())
+                        .set_line(loc.line())
+                        .set_column(loc.column())
+                        .set_context("")
+                        .set_caused_by_error(err);
+                    ex.emit_event();
+                    ex
+                }
+            }
+        )+
+    };
+}
+
+impl_from_error!(
+    serde_json::Error => crate::EXN::DeserializationException,
+    core::str::Utf8Error => crate::EXN::InvalidUTF8BytesException,
+    alloc::string::FromUtf8Error => crate::EXN::InvalidUTF8BytesException,
+);
+
+#[cfg(feature = "std")]
+impl_from_error!(std::io::Error => crate::EXN::IOException);
+
+#[cfg(feature = "http")]
+impl_from_error!(reqwest::Error => crate::EXN::HttpPostException);
 
 pub trait TraitStdResultToOutcome<T, E> {
     /// Add detailed information to an error.
@@ -138,6 +920,10 @@ pub trait TraitStdResultToOutcome<T, E> {
     /// equivalent to `catch("", "")`.
     /// Note that the exception's name will be shown as "DummyException".
     fn catch_(self) -> Outcome<T>;
+
+    /// Like `catch`, but describes the error with key/value `fields` instead of a context string.
+    /// See the `fields!` macro.
+    fn catch_with(self, name: &str, fields: Fields) -> Outcome<T>;
 }
 
 impl<T, E> TraitStdResultToOutcome<T, E> for StdResult<T, E>
@@ -147,116 +933,183 @@ where
     #[track_caller]
     fn catch(self, name: &str, ctx: &str) -> Outcome<T> {
         match self {
-            Ok(v) => {
-                return Ok(v);
-            }
-            Err(e) => {
-                let mut ex = Exception::new();
-                let loc = std::panic::Location::caller();
-                let (file, line, column) = (loc.file(), loc.line(), loc.column());
-                ex.set_name(name)
-                    .set_file(file)
-                    .set_line(line)
-                    .set_column(column)
-                    .set_context(ctx)
-                    .set_caused_by(e);
-                return Err(ex);
-            }
+            Ok(v) => Ok(v),
+            Err(e) => Err(caught(name, Some(ctx), None, Cause::new(e))),
         }
     }
 
     #[track_caller]
     fn catch_(self) -> Outcome<T> {
         match self {
-            Ok(v) => {
-                return Ok(v);
-            }
-            Err(e) => {
-                let mut ex = Exception::new();
-                let loc = std::panic::Location::caller();
-                let (file, line, column) = (loc.file(), loc.line(), loc.column());
-                ex.set_name("")
-                    .set_file(file)
-                    .set_line(line)
-                    .set_column(column)
-                    .set_context("")
-                    .set_caused_by(e);
-                return Err(ex);
-            }
+            Ok(v) => Ok(v),
+            Err(e) => Err(caught("", Some(""), None, Cause::new(e))),
         }
     }
+
+    #[track_caller]
+    fn catch_with(self, name: &str, fields: Fields) -> Outcome<T> {
+        match self {
+            Ok(v) => Ok(v),
+            Err(e) => Err(caught(name, None, Some(fields), Cause::new(e))),
+        }
+    }
+}
+
+pub trait TraitErrorResultToOutcome<T, E> {
+    /// Like `catch`, but the error is also returned by `Error::source`,
+    /// so that it is still reachable once the exception is converted into e.g. `anyhow::Error`.
+    fn catch_error(self, name: &str, ctx: &str) -> Outcome<T>;
+
+    /// equivalent to `catch_error("", "")`.
+    fn catch_error_(self) -> Outcome<T>;
+}
+
+impl<T, E> TraitErrorResultToOutcome<T, E> for StdResult<T, E>
+where
+    E: core::error::Error + Send + Sync + 'static,
+{
+    #[track_caller]
+    fn catch_error(self, name: &str, ctx: &str) -> Outcome<T> {
+        match self {
+            Ok(v) => Ok(v),
+            Err(e) => Err(caught(name, Some(ctx), None, Cause::from_error(e))),
+        }
+    }
+
+    #[track_caller]
+    fn catch_error_(self) -> Outcome<T> {
+        match self {
+            Ok(v) => Ok(v),
+            Err(e) => Err(caught("", Some(""), None, Cause::from_error(e))),
+        }
+    }
+}
+
+/// The frame added by the `catch` family, located at their caller.
+#[track_caller]
+fn caught(name: &str, ctx: Option<&str>, fields: Option<Fields>, cause: Cause) -> Box<Exception> {
+    let mut ex = Exception::new();
+    let loc = ::core::panic::Location::caller();
+    ex.set_name(name)
+        .set_file(loc.file())
+        .set_line(loc.line())
+        .set_column(loc.column());
+    if let Some(ctx) = ctx {
+        ex.set_context(ctx);
+    }
+    if let Some(fields) = fields {
+        ex.set_fields(fields);
+    }
+    ex.set_cause(cause);
+    ex.emit_event();
+    ex
 }
 
 #[macro_export]
 macro_rules! exception {
+    ($name:literal, $ctx:expr) => {
+        $crate::exception!($crate::EXN::unchecked_exception_name($name), $ctx)
+    };
+    ($name:literal) => {
+        $crate::exception!($crate::EXN::unchecked_exception_name($name))
+    };
+    ($name:expr) => {
+        $crate::exception!($name, &$crate::EXN::template_of($name).unwrap_or_default())
+    };
     ($name:expr, $ctx:expr) => {{
-        let mut ex = Exception::new();
-        let loc = std::panic::Location::caller();
-        ex.set_name($name)
+        let mut ex = $crate::Exception::new();
+        let loc = ::core::panic::Location::caller();
+        ex.set_name($crate::EXN::checked_exception_name($name))
             .set_file(loc.file())
             .set_line(loc.line())
             .set_column(loc.column())
-            .set_context($ctx);
+            .set_context($ctx)
+            .capture_backtrace_if_enabled();
+        ex.emit_event();
         ex
     }};
 }
 
 #[macro_export]
 macro_rules! throw {
+    ($name:literal, $ctx:expr) => {
+        $crate::throw!($crate::EXN::unchecked_exception_name($name), $ctx)
+    };
+    ($name:literal) => {
+        $crate::throw!($crate::EXN::unchecked_exception_name($name))
+    };
+    ($name:expr) => {
+        $crate::throw!($name, &$crate::EXN::template_of($name).unwrap_or_default())
+    };
     ($name:expr, $ctx:expr) => {{
-        use crate::exception::Exception;
-        let mut ex = Exception::new();
-        let loc = std::panic::Location::caller();
-        ex.set_name($name)
+        let mut ex = $crate::Exception::new();
+        let loc = ::core::panic::Location::caller();
+        ex.set_name($crate::EXN::checked_exception_name($name))
             .set_file(loc.file())
             .set_line(loc.line())
             .set_column(loc.column())
-            .set_context($ctx);
+            .set_context($ctx)
+            .capture_backtrace_if_enabled();
+        ex.emit_event();
         return Err(ex);
     }};
 }
 
 #[macro_export]
 macro_rules! assert_throw {
+    ($cond:expr, $name:literal, $ctx:expr) => {
+        $crate::assert_throw!($cond, $crate::EXN::unchecked_exception_name($name), $ctx)
+    };
     ($cond:expr, $name:expr, $ctx:expr) => {
         if !($cond) {
-            use crate::exception::Exception;
-            let mut ex = Exception::new();
-            let loc = std::panic::Location::caller();
-            let ctx = format!("Condition: {}\nExplanation: {}", stringify!($cond), $ctx);
-            ex.set_name($name)
+            let mut ex = $crate::Exception::new();
+            let loc = ::core::panic::Location::caller();
+            let ctx = $crate::__private::format!(
+                "Condition: {}\nExplanation: {}",
+                stringify!($cond),
+                $ctx
+            );
+            ex.set_name($crate::EXN::checked_exception_name($name))
                 .set_file(loc.file())
                 .set_line(loc.line())
                 .set_column(loc.column())
-                .set_context(&ctx);
+                .set_context(&ctx)
+                .capture_backtrace_if_enabled();
+            ex.emit_event();
             return Err(ex);
         }
     };
     ($cond:expr, $ctx:expr) => {
         if !($cond) {
-            use crate::exception::Exception;
-            let mut ex = Exception::new();
-            let loc = std::panic::Location::caller();
-            let ctx = format!("Condition: {}\nExplanation: {}", stringify!($cond), $ctx);
-            ex.set_name("AssertionFailedException")
+            let mut ex = $crate::Exception::new();
+            let loc = ::core::panic::Location::caller();
+            let ctx = $crate::__private::format!(
+                "Condition: {}\nExplanation: {}",
+                stringify!($cond),
+                $ctx
+            );
+            ex.set_name($crate::EXN::AssertionFailedException)
                 .set_file(loc.file())
                 .set_line(loc.line())
                 .set_column(loc.column())
-                .set_context(&ctx);
+                .set_context(&ctx)
+                .capture_backtrace_if_enabled();
+            ex.emit_event();
             return Err(ex);
         }
     };
     ($cond:expr) => {
         if !($cond) {
-            use crate::exception::Exception;
-            let mut ex = Exception::new();
-            let loc = std::panic::Location::caller();
-            let ctx = format!("Condition: {}", stringify!($cond));
-            ex.set_name("AssertionFailedException")
+            let mut ex = $crate::Exception::new();
+            let loc = ::core::panic::Location::caller();
+            let ctx = $crate::__private::format!("Condition: {}", stringify!($cond));
+            ex.set_name($crate::EXN::AssertionFailedException)
                 .set_file(loc.file())
                 .set_line(loc.line())
                 .set_column(loc.column())
-                .set_context(&ctx);
+                .set_context(&ctx)
+                .capture_backtrace_if_enabled();
+            ex.emit_event();
             return Err(ex);
         }
     };
@@ -267,7 +1120,7 @@ pub trait TraitStdOptionToOutcome<T> {
     fn ifnone_(self) -> Outcome<T>;
 }
 
-impl<T> TraitStdOptionToOutcome<T> for std::option::Option<T> {
+impl<T> TraitStdOptionToOutcome<T> for Option<T> {
     #[track_caller]
     fn ifnone(self, name: &str, ctx: &str) -> Outcome<T> {
         match self {
@@ -280,7 +1133,448 @@ impl<T> TraitStdOptionToOutcome<T> for std::option::Option<T> {
     fn ifnone_(self) -> Outcome<T> {
         match self {
             Some(t) => Ok(t),
-            None => throw!("", ""),
+            None => {
+                let name = "";
+                throw!(name, "")
+            }
+        }
+    }
+}
+
+/// try/except style handling of an `Outcome`.
+/// A category matches an exception if `ex.is_a(category)`.
+/// Exceptions that do not match are passed on untouched.
+pub trait TraitOutcomeRecover<T> {
+    /// Hand the exception over to `handler` if it matches `category`.
+    fn recover<F>(self, category: &str, handler: F) -> Outcome<T>
+    where
+        F: FnOnce(Box<Exception>) -> Outcome<T>;
+
+    /// Hand the exception over to `handler` if it matches any of `categories`.
+    fn recover_any<F>(self, categories: &[&str], handler: F) -> Outcome<T>
+    where
+        F: FnOnce(Box<Exception>) -> Outcome<T>;
+
+    /// Rename the exception to `name` if it matches `category`.
+    /// Location, context and cause are kept.
+    fn map_name(self, category: &str, name: &str) -> Outcome<T>;
+}
+
+impl<T> TraitOutcomeRecover<T> for Outcome<T> {
+    fn recover<F>(self, category: &str, handler: F) -> Outcome<T>
+    where
+        F: FnOnce(Box<Exception>) -> Outcome<T>,
+    {
+        match self {
+            Err(ex) if ex.is_a(category) => handler(ex),
+            other => other,
+        }
+    }
+
+    fn recover_any<F>(self, categories: &[&str], handler: F) -> Outcome<T>
+    where
+        F: FnOnce(Box<Exception>) -> Outcome<T>,
+    {
+        match self {
+            Err(ex) if categories.iter().any(|c| ex.is_a(c)) => handler(ex),
+            other => other,
+        }
+    }
+
+    fn map_name(self, category: &str, name: &str) -> Outcome<T> {
+        match self {
+            Err(mut ex) if ex.is_a(category) => {
+                ex.set_name(name);
+                Err(ex)
+            }
+            other => other,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    fn open_missing() -> Outcome<std::fs::File> {
+        let path = "!!$%!$>TXT";
+        let f = std::fs::File::open(path).catch_error("IOException", path)?;
+        Ok(f)
+    }
+
+    fn open_missing_twice() -> Outcome<std::fs::File> {
+        let f = open_missing().catch_()?;
+        Ok(f)
+    }
+
+    #[test]
+    fn test_chain() {
+        let ex = open_missing_twice().unwrap_err();
+        let frames: Vec<Frame> = ex.chain().collect();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].as_exception().unwrap().get_name(), "");
+        assert_eq!(frames[1].as_exception().unwrap().get_name(), "IOException");
+        assert!(frames[2].as_exception().is_none());
+
+        let root = ex.root_cause();
+        let ioerr = root.downcast_ref::<std::io::Error>().unwrap();
+        assert_eq!(ioerr.kind(), std::io::ErrorKind::NotFound);
+        assert_eq!(root.to_string(), ioerr.to_string());
+    }
+
+    // `strict-exception-names` warns about every literal name below.
+    #[allow(deprecated)]
+    #[test]
+    fn test_literal_names() {
+        fn fail() -> Outcome<()> {
+            throw!("IntendedException");
+        }
+        let ex = fail().unwrap_err();
+        assert_eq!(ex.get_name(), "IntendedException");
+        assert_eq!(ex.get_context(), Some(""));
+        let ex = exception!("IntendedException", "ctx");
+        assert_eq!(ex.get_context(), Some("ctx"));
+    }
+
+    #[test]
+    fn test_root_cause_without_inner() {
+        fn fail() -> Outcome<()> {
+            let name = "IntendedException";
+            throw!(name, "");
+        }
+        let ex = fail().unwrap_err();
+        assert_eq!(ex.chain().count(), 1);
+        assert_eq!(
+            ex.root_cause().as_exception().unwrap().get_name(),
+            "IntendedException"
+        );
+    }
+
+    #[test]
+    fn test_is_a() {
+        let ex = open_missing().unwrap_err();
+        assert!(ex.is_a(crate::EXN::IOException));
+        assert!(ex.is_a(crate::EXN::UncategorizedException));
+        assert!(!ex.is_a(crate::EXN::HttpPostException));
+    }
+
+    #[test]
+    fn test_code() {
+        let ex = open_missing().unwrap_err();
+        assert_eq!(ex.get_code(), Some(1002));
+        assert_eq!(ex.get_http_status(), 500);
+        assert!(ex
+            .to_string()
+            .starts_with("Exception \"IOException\" (code 1002) occurs at"));
+        let json = serde_json::to_value(&ex).unwrap();
+        assert_eq!(json["code"], 1002);
+    }
+
+    #[test]
+    fn test_recover() {
+        use crate::EXN;
+        let x = open_missing()
+            .map(|_| 1)
+            .recover(EXN::UncategorizedException, |_| Ok(2));
+        assert_eq!(x.unwrap(), 2);
+
+        let ex = open_missing()
+            .map(|_| 1)
+            .recover(EXN::HttpPostException, |_| Ok(2))
+            .unwrap_err();
+        assert_eq!(ex.get_name(), EXN::IOException);
+        let unhandled = open_missing().unwrap_err();
+        assert_eq!((ex.file, ex.line), (unhandled.file.clone(), unhandled.line));
+
+        let x = open_missing()
+            .map(|_| 1)
+            .recover_any(&[EXN::ArithmeticException, EXN::IOException], |ex| {
+                Ok(ex.get_name().len())
+            });
+        assert_eq!(x.unwrap(), EXN::IOException.len());
+
+        let ex = open_missing()
+            .map_name(EXN::IOException, "ConfigMissingException")
+            .unwrap_err();
+        assert_eq!(ex.get_name(), "ConfigMissingException");
+        assert!(ex.root_cause().is::<std::io::Error>());
+    }
+
+    #[test]
+    fn test_unboxed_cause() {
+        let inner = open_missing().unwrap_err();
+        let mut outer = Exception::new();
+        outer.set_name("OuterException").set_caused_by(*inner);
+        let source = core::error::Error::source(outer.as_ref()).unwrap();
+        assert_eq!(
+            source.downcast_ref::<Exception>().unwrap().get_name(),
+            "IOException"
+        );
+        let err = anyhow::Error::from(outer);
+        assert_eq!(err.chain().count(), 3);
+        assert!(err.root_cause().is::<std::io::Error>());
+    }
+
+    #[test]
+    fn test_from_anyhow() {
+        use anyhow::Context;
+        fn startup() -> anyhow::Result<std::fs::File> {
+            let f = std::fs::File::open("!!$%!$>TXT")
+                .context("reading config")
+                .context("starting up")?;
+            Ok(f)
+        }
+        let mut line = 0;
+        let mut startup_outcome = || -> Outcome<std::fs::File> {
+            line = line!() + 1;
+            Ok(startup()?)
+        };
+        let ex = startup_outcome().unwrap_err();
+        let frames: Vec<Frame> = ex.chain().collect();
+        assert_eq!(frames.len(), 4);
+        assert_eq!(ex.line, line);
+        let contexts: Vec<_> = frames[1..3]
+            .iter()
+            .map(|frame| frame.as_exception().unwrap().get_context().unwrap())
+            .collect();
+        assert_eq!(contexts, ["starting up", "reading config"]);
+        let root = ex.root_cause().downcast_ref::<anyhow::Error>().unwrap();
+        let ioerr = root.downcast_ref::<std::io::Error>().unwrap();
+        assert_eq!(ioerr.kind(), std::io::ErrorKind::NotFound);
+        assert_eq!(frames[3].to_string(), ioerr.to_string());
+    }
+
+    #[test]
+    fn test_anyhow_roundtrip() {
+        use anyhow::Context;
+        fn via_anyhow() -> anyhow::Result<std::fs::File> {
+            let f = open_missing().context("via anyhow")?;
+            Ok(f)
+        }
+        let ex = via_anyhow().catch("OuterException", "").unwrap_err();
+        let names: Vec<_> = ex
+            .chain()
+            .filter_map(|frame| frame.as_exception())
+            .map(|ex| ex.get_name())
+            .collect();
+        assert_eq!(
+            names,
+            ["OuterException", "UncategorizedException", "IOException"]
+        );
+        assert!(ex.root_cause().is::<std::io::Error>());
+
+        let err: anyhow::Error = open_missing_twice().unwrap_err().into();
+        assert_eq!(err.chain().count(), 3);
+        let ioerr = err.root_cause().downcast_ref::<std::io::Error>().unwrap();
+        assert_eq!(ioerr.kind(), std::io::ErrorKind::NotFound);
+
+        let ex = via_anyhow().catch_().unwrap_err();
+        let root = core::error::Error::source(ex.as_ref()).unwrap();
+        assert!(root.is::<Exception>());
+        let err = anyhow::Error::from(ex);
+        assert!(err.root_cause().is::<std::io::Error>());
+    }
+
+    #[test]
+    fn test_display_errors() {
+        let boxed: Result<(), Box<dyn std::error::Error + Send + Sync>> =
+            std::fs::File::open("!!$%!$>TXT")
+                .map(|_| ())
+                .map_err(Into::into);
+        let ex = boxed.catch_().unwrap_err();
+        let root = core::error::Error::source(ex.as_ref()).unwrap();
+        assert!(root.is::<std::io::Error>());
+
+        let boxed: Result<(), Box<dyn std::error::Error + Send + Sync>> =
+            Err(open_missing().unwrap_err().into());
+        let ex = boxed.catch("OuterException", "").unwrap_err();
+        assert_eq!(ex.chain().count(), 3);
+        assert!(ex.root_cause().is::<std::io::Error>());
+
+        let ex = Err::<(), String>("no route".to_string())
+            .catch_()
+            .unwrap_err();
+        assert_eq!(
+            ex.root_cause().downcast_ref::<String>().unwrap(),
+            "no route"
+        );
+        assert!(core::error::Error::source(ex.as_ref()).is_none());
+
+        struct Refused(u16);
+        impl fmt::Display for Refused {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "refused on port {}", self.0)
+            }
         }
+        let ex = Err::<(), Refused>(Refused(8080))
+            .catch("ConnectionException", "")
+            .unwrap_err();
+        assert_eq!(ex.root_cause().to_string(), "refused on port 8080");
+        assert!(core::error::Error::source(ex.as_ref()).is_none());
+    }
+
+    #[test]
+    fn test_fields() {
+        let path = "!!$%!$>TXT";
+        let ex = std::fs::File::open(path)
+            .catch_with("IOException", fields![path = path, attempt = 3])
+            .unwrap_err();
+        assert_eq!(ex.get_field("path"), Some(&field_value(path)));
+        assert_eq!(ex.get_field("attempt").and_then(|v| v.as_u64()), Some(3));
+        assert!(ex
+            .to_string()
+            .contains("\nFields: path=\"!!$%!$>TXT\", attempt=3\n"));
+
+        let mut ex = ex;
+        ex.set_field("attempt", &4).set_field("fatal", &true);
+        let keys: Vec<_> = ex.get_fields().iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, ["path", "attempt", "fatal"]);
+
+        let json = serde_json::to_value(&ex).unwrap();
+        assert_eq!(json["fields"]["attempt"], 4);
+        let ex2: Exception = serde_json::from_value(json).unwrap();
+        assert_eq!(ex2.get_field("fatal"), Some(&serde_json::Value::Bool(true)));
+
+        // `serde_json::Value` sorts the keys, but the JSON text keeps their order.
+        let ex2: Exception = serde_json::from_str(&serde_json::to_string(&ex).unwrap()).unwrap();
+        assert_eq!(ex2.get_fields(), ex.get_fields());
+    }
+
+    #[test]
+    fn test_backtrace() {
+        let mut ex = open_missing().unwrap_err();
+        ex.capture_backtrace();
+        assert!(ex.to_string().contains("\nBacktrace:\n"));
+        let mut outer = Exception::new();
+        outer.capture_backtrace().set_caused_by(ex);
+        assert!(outer.backtrace.is_none());
+        assert!(outer.get_backtrace().is_some());
+        // the backtrace is printed once, under the whole chain.
+        let text = outer.to_string();
+        assert_eq!(text.matches("\nBacktrace:\n").count(), 1);
+        assert!(text.find("No such file").unwrap() < text.find("Backtrace:").unwrap());
+    }
+
+    #[test]
+    fn test_serde_roundtrip() {
+        let ex = open_missing_twice().unwrap_err();
+        let json = serde_json::to_string(&ex).unwrap();
+        let ex2: Box<Exception> = serde_json::from_str(&json).unwrap();
+        assert_eq!(ex.to_string(), ex2.to_string());
+        assert_eq!(ex2.chain().count(), 3);
+        assert_eq!(
+            ex2.chain()
+                .nth(1)
+                .unwrap()
+                .as_exception()
+                .unwrap()
+                .get_context(),
+            Some("!!$%!$>TXT")
+        );
+        assert!(ex2.root_cause().is::<String>());
+        assert_eq!(json, serde_json::to_string(&ex2).unwrap());
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        fn fail(ctx: &str) -> Outcome<()> {
+            Err(std::io::Error::other(ctx.to_string())).catch(crate::EXN::IOException, ctx)?;
+            Ok(())
+        }
+        fn outer(ctx: &str) -> Outcome<()> {
+            fail(ctx).catch_with("FingerprintException", fields![ctx = ctx])?;
+            Ok(())
+        }
+        let a = outer("a").unwrap_err().fingerprint();
+        let b = outer("b").unwrap_err().fingerprint();
+        assert_eq!(a, b);
+        assert_ne!(a, fail("a").unwrap_err().fingerprint());
+        let mut other = outer("a").unwrap_err();
+        other.set_line(other.get_line() + 1);
+        assert_ne!(a, other.fingerprint());
+    }
+
+    #[test]
+    fn test_question_mark_conversions() {
+        let mut line = 0;
+        let mut open = || -> Outcome<()> {
+            line = line!() + 1;
+            std::fs::File::open("!!$%!$>TXT")?;
+            Ok(())
+        };
+        fn parse() -> Outcome<u8> {
+            Ok(serde_json::from_str("\"x\"")?)
+        }
+        fn decode() -> Outcome<String> {
+            Ok(String::from_utf8(vec![0xff])?)
+        }
+        fn decode_str() -> Outcome<usize> {
+            Ok(std::str::from_utf8(&std::hint::black_box([0xff]))?.len())
+        }
+        let ex = open().unwrap_err();
+        assert_eq!(ex.get_name(), crate::EXN::IOException);
+        assert_eq!((ex.get_file(), ex.get_line()), (file!(), line));
+        assert!(ex.get_caused_by().unwrap().is::<std::io::Error>());
+        assert_eq!(
+            parse().unwrap_err().get_name(),
+            crate::EXN::DeserializationException
+        );
+        assert_eq!(
+            decode().unwrap_err().get_name(),
+            crate::EXN::InvalidUTF8BytesException
+        );
+        assert_eq!(
+            decode_str().unwrap_err().get_name(),
+            crate::EXN::InvalidUTF8BytesException
+        );
+    }
+
+    #[test]
+    fn test_metadata() -> Outcome<()> {
+        let mut inner = Exception::new();
+        inner.set_name("InnerException").capture_metadata();
+        let meta = inner.get_metadata().unwrap().clone();
+        let mut outer = Exception::new();
+        outer.capture_metadata().set_caused_by(inner);
+        assert!(outer.get_own_metadata().is_none());
+        assert_eq!(outer.get_metadata(), Some(&meta));
+        assert_eq!(outer.to_string().matches("Raised in: thread").count(), 1);
+
+        let json = crate::obj_to_json(&outer)?;
+        let back: Exception = crate::json_to_obj(&json)?;
+        assert_eq!(back.get_metadata(), Some(&meta));
+        Ok(())
+    }
+}
+
+#[cfg(all(test, not(feature = "std")))]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Outcome<u32> {
+        if text.is_empty() {
+            throw!(crate::EXN::DeserializationException);
+        }
+        text.parse::<u32>()
+            .catch_with(crate::EXN::DataFormatException, fields![text = text])
+    }
+
+    #[test]
+    fn test_throw_catch() {
+        assert_eq!(parse("42").unwrap(), 42);
+
+        let ex = parse("").unwrap_err();
+        assert!(ex.is_a(crate::EXN::UncategorizedException));
+        assert_eq!(ex.get_context(), Some("The input cannot be deserialized"));
+
+        let ex = parse("4x").catch("ConfigException", "port").unwrap_err();
+        assert_eq!(ex.chain().count(), 3);
+        let inner = ex.get_caused_by().unwrap().as_exception().unwrap();
+        assert!(inner.is_a(crate::EXN::DeserializationException));
+        assert!(ex.root_cause().is::<core::num::ParseIntError>());
+        let text = ex.to_string();
+        assert!(text.contains("\nContext: port\n"));
+        assert!(text.contains("\nFields: text=\"4x\"\n"));
+        assert!(text.contains("invalid digit found in string"));
     }
 }
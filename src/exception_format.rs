@@ -0,0 +1,467 @@
+#[cfg(feature = "tracing")]
+use crate::logging::SpanInfo;
+#[cfg(not(feature = "std"))]
+use crate::no_std_prelude::*;
+use crate::*;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+/// How an `Exception` is rendered by `Display` and by `Exception::render`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExceptionFormat {
+    /// The multi-line form with one "Caused by:" section per frame. This is the default.
+    Classic,
+    /// A single line, for log lines. Frames are joined by " <- ".
+    Compact,
+    /// An indented tree for terminals, optionally coloured with ANSI escape codes.
+    Pretty { ansi: bool },
+    /// A single-line JSON object, as produced by `serde_json`, for log shippers.
+    Json,
+}
+
+static FORMAT: AtomicU8 = AtomicU8::new(0);
+
+/// Set the format of `{}`. `{:#}` always renders `ExceptionFormat::Compact`.
+pub fn set_exception_format(format: ExceptionFormat) {
+    let val = match format {
+        ExceptionFormat::Classic => 0,
+        ExceptionFormat::Compact => 1,
+        ExceptionFormat::Pretty { ansi: false } => 2,
+        ExceptionFormat::Pretty { ansi: true } => 3,
+        ExceptionFormat::Json => 4,
+    };
+    FORMAT.store(val, Ordering::Relaxed);
+}
+
+pub fn exception_format() -> ExceptionFormat {
+    match FORMAT.load(Ordering::Relaxed) {
+        1 => ExceptionFormat::Compact,
+        2 => ExceptionFormat::Pretty { ansi: false },
+        3 => ExceptionFormat::Pretty { ansi: true },
+        4 => ExceptionFormat::Json,
+        _ => ExceptionFormat::Classic,
+    }
+}
+
+#[cfg(feature = "std")]
+static SOURCE_SNIPPET: EnvToggle = EnvToggle::new("XUANMI_SOURCE_SNIPPET");
+
+/// Whether `ExceptionFormat::Classic` shows the source line of each frame, with a caret under the column.
+/// Unless overridden by `set_source_snippet_enabled`,
+/// it is enabled iff the env-var `XUANMI_SOURCE_SNIPPET` is set to a value other than "0".
+/// The line is read at runtime from the recorded path, which is relative to the workspace root,
+/// so this is meant for dev builds run by `cargo run` or `cargo test` from within the workspace.
+/// The path is resolved against the working directory and each of its ancestors,
+/// since `cargo test` runs in the directory of the package rather than of the workspace.
+#[cfg(feature = "std")]
+pub fn source_snippet_enabled() -> bool {
+    SOURCE_SNIPPET.get()
+}
+
+#[cfg(feature = "std")]
+pub fn set_source_snippet_enabled(enabled: bool) {
+    SOURCE_SNIPPET.set(enabled);
+}
+
+impl Exception {
+    pub fn render(&self, format: ExceptionFormat) -> String {
+        match format {
+            ExceptionFormat::Classic => render_classic(self),
+            ExceptionFormat::Compact => render_compact(self),
+            ExceptionFormat::Pretty { ansi } => {
+                let mut out = String::new();
+                write_pretty(self, 0, ansi, &mut out);
+                #[cfg(feature = "std")]
+                if let Some(bt) = self.get_backtrace() {
+                    out += &format!("\n{}", paint(ansi, DIM, "backtrace:"));
+                    out += &format!("\n{}", bt.to_string().trim_end());
+                }
+                out + "\n"
+            }
+            ExceptionFormat::Json => match serde_json::to_value(self) {
+                Ok(mut json) => {
+                    redact_json(&mut json);
+                    json.to_string()
+                }
+                Err(e) => format!(
+                    "{{\"name\":{:?},\"error\":{:?}}}",
+                    self.get_name(),
+                    e.to_string()
+                ),
+            },
+        }
+    }
+}
+
+/// Secrets are redacted, see `set_redaction_policy`.
+#[cfg(feature = "std")]
+pub(crate) fn fields_text(fields: &Fields) -> String {
+    let policy = redaction_policy();
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|(k, v)| {
+            if policy.is_secret_key(k) {
+                format!("{}={}", k, REDACTED)
+            } else {
+                let mut v = v.clone();
+                policy.redact_json(&mut v);
+                format!("{}={}", k, v)
+            }
+        })
+        .collect();
+    fields.join(", ")
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn fields_text(fields: &Fields) -> String {
+    let fields: Vec<String> = fields.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+    fields.join(", ")
+}
+
+/// Without `std` there is no redaction policy.
+#[cfg(not(feature = "std"))]
+fn redact_text(text: &str) -> String {
+    text.to_string()
+}
+
+#[cfg(not(feature = "std"))]
+fn redact_json(_: &mut serde_json::Value) {}
+
+#[cfg(feature = "tracing")]
+fn spans_text(spans: &[SpanInfo]) -> String {
+    let spans: Vec<String> = spans
+        .iter()
+        .map(|span| format!("{}{{{}}}", span.name, fields_text(&span.fields)))
+        .collect();
+    spans.join(" > ")
+}
+
+fn location_text(ex: &Exception) -> String {
+    let mut loc = ex.get_file().to_string();
+    if ex.get_line() > 0 {
+        loc += &format!(":{}", ex.get_line());
+    }
+    if ex.get_column() > 0 {
+        loc += &format!(":{}", ex.get_column());
+    }
+    loc
+}
+
+/// The source line in the style of rustc, e.g.
+/// ```text
+///    |
+/// 42 |     let x = compute()?;
+///    |             ^
+/// ```
+/// `None` if the file cannot be read or has no such line.
+#[cfg(feature = "std")]
+fn source_snippet(file: &str, line: u32, column: u32) -> Option<String> {
+    if line == 0 {
+        return None;
+    }
+    let cwd = std::env::current_dir().ok()?;
+    let source = read_source(&cwd, file)?;
+    let code = source.lines().nth(line as usize - 1)?.trim_end();
+    let gutter = " ".repeat(line.to_string().len());
+    let mut msg = format!("{} |\n{} | {}", gutter, line, code);
+    if column > 0 {
+        // Keep tabs, so that the caret lines up however wide they are shown.
+        let pad: String = code
+            .chars()
+            .take(column as usize - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        msg += &format!("\n{} | {}^", gutter, pad);
+    }
+    Some(msg)
+}
+
+/// Read `file`, relative to `dir` or to the closest ancestor of `dir` where it exists.
+#[cfg(feature = "std")]
+fn read_source(dir: &std::path::Path, file: &str) -> Option<String> {
+    dir.ancestors()
+        .find_map(|dir| std::fs::read_to_string(dir.join(file)).ok())
+}
+
+fn render_classic(ex: &Exception) -> String {
+    let mut msg: String = format!("Exception \"{}\"", ex.get_name());
+    if let Some(code) = ex.get_code() {
+        msg += &format!(" (code {})", code);
+    }
+    msg += &format!(" occurs at \"{}\"", location_text(ex));
+    #[cfg(feature = "std")]
+    if source_snippet_enabled() {
+        if let Some(snippet) = source_snippet(ex.get_file(), ex.get_line(), ex.get_column()) {
+            msg += &format!("\n{}", snippet);
+        }
+    }
+    if let Some(ctx) = ex.get_context() {
+        let ctx = ctx.trim();
+        if !ctx.is_empty() {
+            msg += &format!("\nContext: {}", redact_text(ctx));
+        }
+    }
+    if !ex.get_fields().is_empty() {
+        msg += &format!("\nFields: {}", fields_text(ex.get_fields()));
+    }
+    #[cfg(feature = "tracing")]
+    if !ex.get_spans().is_empty() {
+        msg += &format!("\nSpan: {}", spans_text(ex.get_spans()));
+    }
+    #[cfg(feature = "std")]
+    if let Some(metadata) = ex.get_own_metadata() {
+        msg += &format!("\nRaised in: {}", metadata);
+    }
+    if let Some(frame) = ex.get_caused_by() {
+        let inner = match (frame.as_exception(), frame.downcast_ref::<MultiException>()) {
+            (Some(inner), _) => render_classic(inner),
+            (None, Some(multi)) => render_multi_classic(multi),
+            (None, None) => redact_text(&frame.to_string()),
+        };
+        msg += &format!("\nCaused by:\n{}", inner);
+    }
+    #[cfg(feature = "std")]
+    if let Some(bt) = ex.get_own_backtrace() {
+        msg += &format!("\nBacktrace:\n{}", bt.to_string().trim_end());
+    }
+    msg += "\n";
+    msg
+}
+
+/// Every child is rendered as an indented subtree.
+pub(crate) fn render_multi_classic(multi: &MultiException) -> String {
+    let mut msg = format!("{} exceptions occur:", multi.len());
+    for (i, ex) in multi.get_children().iter().enumerate() {
+        msg += &format!("\n[{}] ", i);
+        let text = render_classic(ex);
+        for (j, line) in text.trim_end().lines().enumerate() {
+            if j > 0 {
+                msg += "\n    ";
+            }
+            msg += line;
+        }
+    }
+    msg
+}
+
+fn one_line(text: &str) -> String {
+    let lines: Vec<&str> = text.lines().map(|line| line.trim()).collect();
+    lines.join(" ")
+}
+
+fn render_compact(ex: &Exception) -> String {
+    let mut msg = String::new();
+    if !ex.get_name().is_empty() {
+        msg += ex.get_name();
+        if let Some(code) = ex.get_code() {
+            msg += &format!(" (code {})", code);
+        }
+        msg += " ";
+    }
+    msg += &format!("at {}", location_text(ex));
+    if let Some(ctx) = ex.get_context() {
+        let ctx = ctx.trim();
+        if !ctx.is_empty() {
+            msg += &format!(": {}", one_line(&redact_text(ctx)));
+        }
+    }
+    if !ex.get_fields().is_empty() {
+        msg += &format!(" [{}]", fields_text(ex.get_fields()));
+    }
+    if let Some(frame) = ex.get_caused_by() {
+        msg += " <- ";
+        if let Some(inner) = frame.as_exception() {
+            msg += &render_compact(inner);
+        } else if let Some(multi) = frame.downcast_ref::<MultiException>() {
+            let children: Vec<String> = multi.get_children().iter().map(render_compact).collect();
+            msg += &format!("[{}]", children.join(" | "));
+        } else {
+            msg += &one_line(&redact_text(&frame.to_string()));
+        }
+    }
+    msg
+}
+
+const BOLD_RED: &str = "\x1b[1;31m";
+const DIM: &str = "\x1b[2m";
+const YELLOW: &str = "\x1b[33m";
+
+fn paint(ansi: bool, style: &str, text: &str) -> String {
+    if ansi {
+        format!("{}{}\x1b[0m", style, text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// The first line is written at the cursor; the following lines are indented by `indent`.
+fn write_pretty(ex: &Exception, indent: usize, ansi: bool, out: &mut String) {
+    let pad = " ".repeat(indent);
+    let mut title = format!("Exception \"{}\"", ex.get_name());
+    if let Some(code) = ex.get_code() {
+        title += &format!(" (code {})", code);
+    }
+    *out += &paint(ansi, BOLD_RED, &title);
+    let loc = format!("at {}", location_text(ex));
+    *out += &format!("\n{}   {}", pad, paint(ansi, DIM, &loc));
+    if let Some(ctx) = ex.get_context() {
+        let ctx = ctx.trim();
+        if !ctx.is_empty() {
+            let ctx = redact_text(ctx).replace('\n', &format!("\n{}            ", pad));
+            *out += &format!("\n{}   context: {}", pad, ctx);
+        }
+    }
+    if !ex.get_fields().is_empty() {
+        *out += &format!("\n{}   fields: {}", pad, fields_text(ex.get_fields()));
+    }
+    #[cfg(feature = "tracing")]
+    if !ex.get_spans().is_empty() {
+        *out += &format!("\n{}   span: {}", pad, spans_text(ex.get_spans()));
+    }
+    #[cfg(feature = "std")]
+    if let Some(metadata) = ex.get_own_metadata() {
+        *out += &format!("\n{}   raised in: {}", pad, metadata);
+    }
+    let frame = match ex.get_caused_by() {
+        Some(frame) => frame,
+        None => return,
+    };
+    *out += &format!("\n{}└─ ", pad);
+    if let Some(inner) = frame.as_exception() {
+        write_pretty(inner, indent + 3, ansi, out);
+    } else if let Some(multi) = frame.downcast_ref::<MultiException>() {
+        *out += &format!("{} exceptions:", multi.len());
+        for (i, child) in multi.get_children().iter().enumerate() {
+            let label = format!("[{}] ", i);
+            *out += &format!("\n{}   {}", pad, label);
+            write_pretty(child, indent + 3 + label.len(), ansi, out);
+        }
+    } else {
+        let text = redact_text(&frame.to_string());
+        let text = text.trim_end().replace('\n', &format!("\n{}   ", pad));
+        *out += &paint(ansi, YELLOW, &text);
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{read_source, source_snippet};
+    use crate::*;
+
+    fn inner() -> Outcome<()> {
+        throw!(EXN::IOException, "Disk\nis full");
+    }
+
+    fn outer() -> Outcome<()> {
+        inner().catch_with("SaveException", fields![path = "/tmp/x"])?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_compact() {
+        let (mut inner_line, mut outer_line) = (0, 0);
+        let mut inner = || -> Outcome<()> {
+            inner_line = line!() + 1;
+            throw!(EXN::IOException, "Disk\nis full");
+        };
+        let mut outer = || -> Outcome<()> {
+            outer_line = line!() + 1;
+            inner().catch_with("SaveException", fields![path = "/tmp/x"])?;
+            Ok(())
+        };
+        let ex = outer().unwrap_err();
+        let expected = format!(
+            "SaveException at {f}:{}:21 [path=\"/tmp/x\"] <- IOException (code 1002) at {f}:{}:13: Disk is full",
+            outer_line,
+            inner_line,
+            f = file!()
+        );
+        assert_eq!(ex.render(ExceptionFormat::Compact), expected);
+        assert_eq!(format!("{:#}", ex), expected);
+    }
+
+    #[test]
+    fn test_pretty() {
+        let ex = outer().unwrap_err();
+        let text = ex.render(ExceptionFormat::Pretty { ansi: false });
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "Exception \"SaveException\"");
+        assert_eq!(lines[2], "   fields: path=\"/tmp/x\"");
+        assert_eq!(lines[3], "└─ Exception \"IOException\" (code 1002)");
+        assert_eq!(lines[5], "      context: Disk");
+        assert_eq!(lines[6], "               is full");
+
+        let text = ex.render(ExceptionFormat::Pretty { ansi: true });
+        assert!(text.starts_with("\x1b[1;31mException \"SaveException\"\x1b[0m"));
+    }
+
+    #[test]
+    fn test_json() {
+        let ex = outer().unwrap_err();
+        let text = ex.render(ExceptionFormat::Json);
+        assert!(!text.contains('\n'));
+        let ex2: Exception = json_to_obj(&text).unwrap();
+        assert_eq!(ex2.render(ExceptionFormat::Classic), ex.to_string());
+    }
+
+    #[test]
+    fn test_json_field_order() {
+        let mut ex = Exception::new();
+        ex.set_name("OrderException").set_fields(fields![
+            zone = "b",
+            attempt = 2,
+            addr = "10.0.0.1"
+        ]);
+        let text = ex.render(ExceptionFormat::Json);
+        let zone = text.find("\"zone\"").unwrap();
+        let attempt = text.find("\"attempt\"").unwrap();
+        let addr = text.find("\"addr\"").unwrap();
+        assert!(zone < attempt && attempt < addr);
+        assert_eq!(text, obj_to_json(ex.as_ref()).unwrap());
+    }
+
+    #[test]
+    fn test_source_snippet() {
+        let ex = inner().unwrap_err();
+        let snippet = source_snippet(ex.get_file(), ex.get_line(), ex.get_column()).unwrap();
+        let gutter = " ".repeat(ex.get_line().to_string().len());
+        let expected = format!(
+            "{0} |\n{1} |         throw!(EXN::IOException, \"Disk\\nis full\");\n{0} |         ^",
+            gutter,
+            ex.get_line()
+        );
+        assert_eq!(snippet, expected);
+        assert!(source_snippet("no/such/file.rs", 1, 1).is_none());
+        // as from a workspace member, whose directory is below the workspace root.
+        let member_dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("ffi");
+        assert!(read_source(&member_dir, ex.get_file()).is_some());
+        assert!(source_snippet(ex.get_file(), 1_000_000, 1).is_none());
+    }
+
+    #[test]
+    fn test_redaction() {
+        fn login() -> Outcome<()> {
+            Err("401 for https://x.io/login?token=abc123").catch_with(
+                "LoginException",
+                fields![password = "hunter2", user = "bob"],
+            )?;
+            Ok(())
+        }
+        let ex = login().unwrap_err();
+        for format in [
+            ExceptionFormat::Classic,
+            ExceptionFormat::Compact,
+            ExceptionFormat::Pretty { ansi: false },
+            ExceptionFormat::Json,
+        ] {
+            let text = ex.render(format);
+            assert!(!text.contains("abc123"), "{}", text);
+            assert!(!text.contains("hunter2"), "{}", text);
+            assert!(text.contains("bob"), "{}", text);
+        }
+        // what is sent to peers is left intact.
+        assert!(obj_to_json(&ex).unwrap().contains("hunter2"));
+        assert!(!obj_to_json_pretty_redacted(&ex)
+            .unwrap()
+            .contains("hunter2"));
+    }
+}
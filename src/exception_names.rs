@@ -1,20 +1,670 @@
-macro_rules! register_exception_names {
-    ($($arg:ident),+) => {
+#[cfg(not(feature = "std"))]
+use crate::no_std_prelude::*;
+#[cfg(feature = "std")]
+use crate::*;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+#[cfg(all(feature = "std", feature = "strict-exception-names"))]
+use std::collections::HashSet;
+#[cfg(all(feature = "std", feature = "strict-exception-names"))]
+use std::sync::Mutex;
+#[cfg(feature = "std")]
+use std::sync::{OnceLock, RwLock};
+
+/// Declare exception names, each as a `&str` constant, together with a table
+/// `DEFINED_EXCEPTIONS` to be passed to `register_exceptions` at start-up.
+/// Each entry reads `Name: Parent [code, http_status] => "default context";`,
+/// where everything after the name is optional, and is preceded by its doc comment.
+/// Since the parent is a path to another constant, a misspelt category fails to compile.
+/// ```ignore
+/// define_exception! {
+///     /// The configuration file is missing a mandatory key.
+///     ConfigKeyException: EXN::DeserializationException [5001] => "A required key is absent";
+/// }
+/// ```
+/// Each invocation defines its table, so a second invocation in the same module
+/// must name its own table with a leading `table NAME;`:
+/// ```ignore
+/// define_exception! {
+///     table NET_EXCEPTIONS;
+///     PeerResetException: EXN::IOException [5101];
+/// }
+/// ```
+/// Use the generated constants rather than string literals with `throw!`:
+/// with the feature `strict-exception-names`, a literal name triggers a deprecation warning,
+/// and a name that is not registered when thrown is reported at runtime, see `checked_exception_name`.
+#[macro_export]
+macro_rules! define_exception {
+    (@opt) => { None };
+    (@opt $val:expr) => { Some($val) };
+    (@table $table:ident; $(
+        $(#[doc = $doc:literal])*
+        $name:ident $(: $parent:path)? $([$code:literal $(, $status:literal)?])? $(=> $template:literal)?
+    );+ $(;)?) => {
         $(
-            pub const $arg: &'static str = stringify!($arg);
+            $(#[doc = $doc])*
+            #[allow(non_upper_case_globals)]
+            pub const $name: &'static str = stringify!($name);
         )+
+
+        pub const $table: &[$crate::exception_names::ExceptionDef] = &[
+            $(
+                $crate::exception_names::ExceptionDef {
+                    name: stringify!($name),
+                    parent: $crate::define_exception!(@opt $($parent)?),
+                    code: $crate::define_exception!(@opt $($code)?),
+                    http_status: $crate::define_exception!(@opt $($($status)?)?),
+                    doc: &[$($doc),*],
+                    template: $crate::define_exception!(@opt $($template)?),
+                },
+            )+
+        ];
+    };
+    (table $table:ident; $($entries:tt)+) => {
+        $crate::define_exception!(@table $table; $($entries)+);
     };
+    ($($entries:tt)+) => {
+        $crate::define_exception!(@table DEFINED_EXCEPTIONS; $($entries)+);
+    };
+}
+
+/// An entry generated by `define_exception!`.
+#[derive(Debug, Clone, Copy)]
+pub struct ExceptionDef {
+    pub name: &'static str,
+    pub parent: Option<&'static str>,
+    pub code: Option<u32>,
+    pub http_status: Option<u16>,
+    /// The lines of the doc comment.
+    pub doc: &'static [&'static str],
+    pub template: Option<&'static str>,
 }
 
-register_exception_names!(
-    UncategorizedException,
-    DummyException,
-    HttpPostException,
-    SerializationException,
-    DeserializationException,
-    IndexOutOfBoundException,
-    InvalidUTF8BytesException,
-    ArithmeticException,
-    IOException,
-    PathResolutionException
-);
+impl ExceptionDef {
+    pub fn get_doc(&self) -> String {
+        let lines: Vec<&str> = self.doc.iter().map(|line| line.trim()).collect();
+        lines.join("\n")
+    }
+}
+
+crate::define_exception! {
+    /// The root of all built-in categories. A name that is not registered is not `is_a(UncategorizedException)`,
+    /// and gets `DEFAULT_HTTP_STATUS` rather than the status of this entry.
+    UncategorizedException [1000, 500] => "An uncategorized error occurs";
+    DummyException: UncategorizedException [1001];
+    /// An error from the file system, the network, or any other device.
+    IOException: UncategorizedException [1002] => "An I/O operation fails";
+    HttpPostException: IOException [1003, 502] => "An HTTP POST request fails";
+    PathResolutionException: IOException [1004] => "A path cannot be resolved";
+    SerializationException: UncategorizedException [1005] => "An object cannot be serialized";
+    DeserializationException: UncategorizedException [1006, 400] => "The input cannot be deserialized";
+    InvalidUTF8BytesException: DeserializationException [1007] => "The bytes are not valid UTF-8";
+    IndexOutOfBoundException: UncategorizedException [1008] => "An index is out of bound";
+    ArithmeticException: UncategorizedException [1009] => "An arithmetic operation fails";
+    /// A panic turned into an exception by `outcome_from_panic` or the panic hook.
+    PanicException: UncategorizedException [1010] => "A panic occurs";
+    /// Reports several independent failures; each of them is a child of its cause.
+    MultiException: UncategorizedException [1011] => "Several exceptions occur";
+    /// Thrown by `retry` once its policy gives up.
+    RetryExhaustedException: UncategorizedException [1012] => "Every attempt fails";
+    /// A JSON field is present but has an unexpected type or value.
+    JsonInvalidFieldException: DeserializationException [1013] => "A JSON field is invalid";
+    /// A required JSON field is absent.
+    JsonNoRequiredFieldException: DeserializationException [1014] => "A required JSON field is absent";
+    /// The input is well-formed, but does not follow the expected protocol.
+    DataFormatException: DeserializationException [1015] => "The data format is unexpected";
+    /// Thrown by `assert_throw!` when no name is given.
+    AssertionFailedException: UncategorizedException [1016] => "An assertion fails";
+    /// A null pointer is received through the C interface.
+    NullPointerException: UncategorizedException [1017] => "A pointer is null";
+}
+
+/// Called by `throw!` and its siblings when the name is a string literal.
+#[doc(hidden)]
+#[cfg_attr(
+    feature = "strict-exception-names",
+    deprecated(note = "use a constant declared by `define_exception!` as the exception name")
+)]
+pub const fn unchecked_exception_name(name: &'static str) -> &'static str {
+    name
+}
+
+/// Called by `throw!` and its siblings with every name, literal or not.
+/// With the feature `strict-exception-names`, a name that is not registered,
+/// e.g. a misspelt one held in a variable, is reported once by a warning,
+/// through `tracing` if enabled and to stderr otherwise.
+#[doc(hidden)]
+#[inline]
+pub fn checked_exception_name(name: &str) -> &str {
+    #[cfg(all(feature = "std", feature = "strict-exception-names"))]
+    warn_if_unregistered(name);
+    name
+}
+
+/// Whether a warning is issued, i.e. `name` is not registered and has not been warned about yet.
+#[cfg(all(feature = "std", feature = "strict-exception-names"))]
+fn warn_if_unregistered(name: &str) -> bool {
+    static WARNED: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+    let registered = {
+        let map = registry().read().unwrap_or_else(|e| e.into_inner());
+        map.contains_key(name)
+    };
+    if registered {
+        return false;
+    }
+    let mut warned = WARNED
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if !warned.insert(name.to_string()) {
+        return false;
+    }
+    drop(warned);
+    #[cfg(feature = "tracing")]
+    tracing::warn!(exception = name, "the exception name is not registered");
+    #[cfg(not(feature = "tracing"))]
+    eprintln!("the exception name \"{}\" is not registered", name);
+    true
+}
+
+/// The HTTP status of an exception whose name is neither registered with a status
+/// nor registered under a category with a status.
+pub const DEFAULT_HTTP_STATUS: u16 = 500;
+
+#[cfg(feature = "std")]
+#[derive(Default)]
+struct NameInfo {
+    parent: Option<String>,
+    code: Option<u32>,
+    http_status: Option<u16>,
+    doc: Option<String>,
+    template: Option<String>,
+}
+
+#[cfg(feature = "std")]
+impl From<&ExceptionDef> for NameInfo {
+    fn from(def: &ExceptionDef) -> Self {
+        NameInfo {
+            parent: def.parent.map(|p| p.to_string()),
+            code: def.code,
+            http_status: def.http_status,
+            doc: Some(def.get_doc()).filter(|doc| !doc.is_empty()),
+            template: def.template.map(|t| t.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+fn registry() -> &'static RwLock<HashMap<String, NameInfo>> {
+    static REGISTRY: OnceLock<RwLock<HashMap<String, NameInfo>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        let map = DEFINED_EXCEPTIONS
+            .iter()
+            .map(|def| (def.name.to_string(), NameInfo::from(def)))
+            .collect();
+        RwLock::new(map)
+    })
+}
+
+/// Register `name` as a sub-category of `parent`, or as a root category if `parent` is `None`.
+/// Re-registering a name replaces its parent.
+/// Throws if the registration would make the hierarchy cyclic.
+#[cfg(feature = "std")]
+pub fn register_exception_name(name: &str, parent: Option<&str>) -> Outcome<()> {
+    // The check and the insert share one critical section, so that concurrent registrations
+    // cannot make a cycle. `throw!` reads the registry, so the lock is released first.
+    let mut map = registry().write().unwrap_or_else(|e| e.into_inner());
+    if let Some(msg) = cycle_error(&map, name, parent) {
+        drop(map);
+        throw!(UncategorizedException, &msg);
+    }
+    let info = map.entry(name.to_string()).or_default();
+    info.parent = parent.map(|p| p.to_string());
+    Ok(())
+}
+
+/// Why registering `name` under `parent` is rejected, if it makes the hierarchy cyclic.
+#[cfg(feature = "std")]
+fn cycle_error(
+    map: &HashMap<String, NameInfo>,
+    name: &str,
+    parent: Option<&str>,
+) -> Option<String> {
+    let parent = parent?;
+    if !subclass_in(map, parent, name) {
+        return None;
+    }
+    Some(format!(
+        "Registering \"{}\" under \"{}\" makes the hierarchy cyclic",
+        name, parent
+    ))
+}
+
+/// Register the table generated by `define_exception!`.
+/// Throws if an entry makes the hierarchy cyclic, or takes a code of another name,
+/// in which case the entries before it stay registered.
+#[cfg(feature = "std")]
+pub fn register_exceptions(defs: &[ExceptionDef]) -> Outcome<()> {
+    for def in defs {
+        // Checks and insert share one critical section, like in `register_exception_name`.
+        let mut map = registry().write().unwrap_or_else(|e| e.into_inner());
+        if let Some(code) = def.code {
+            if let Some(other) = code_owner(&map, code, def.name) {
+                drop(map);
+                throw!(
+                    UncategorizedException,
+                    &format!("Code {} is already assigned to \"{}\"", code, other)
+                );
+            }
+        }
+        if let Some(msg) = cycle_error(&map, def.name, def.parent) {
+            drop(map);
+            throw!(UncategorizedException, &msg);
+        }
+        let new = NameInfo::from(def);
+        let info = map.entry(def.name.to_string()).or_default();
+        info.parent = new.parent;
+        info.code = new.code.or(info.code);
+        info.http_status = new.http_status.or(info.http_status);
+        info.doc = new.doc.or(info.doc.take());
+        info.template = new.template.or(info.template.take());
+    }
+    Ok(())
+}
+
+/// Every registered name, sorted.
+#[cfg(feature = "std")]
+pub fn known_exceptions() -> Vec<String> {
+    let map = registry().read().unwrap_or_else(|e| e.into_inner());
+    let mut names: Vec<String> = map.keys().cloned().collect();
+    names.sort();
+    names
+}
+
+/// The doc comment `name` is declared with, if any.
+#[cfg(feature = "std")]
+pub fn doc_of(name: &str) -> Option<String> {
+    let map = registry().read().unwrap_or_else(|e| e.into_inner());
+    map.get(name)?.doc.clone()
+}
+
+/// The default context of `name`, used by `throw!(name)`.
+#[cfg(feature = "std")]
+pub fn template_of(name: &str) -> Option<String> {
+    let map = registry().read().unwrap_or_else(|e| e.into_inner());
+    map.get(name)?.template.clone()
+}
+
+/// Assign a numeric code and an HTTP status to a registered `name`.
+/// Throws if `name` is not registered, or if `code` is already taken by another name.
+#[cfg(feature = "std")]
+pub fn set_exception_code(name: &str, code: u32, http_status: u16) -> Outcome<()> {
+    let mut map = registry().write().unwrap_or_else(|e| e.into_inner());
+    // `throw!` emits an event that reads the registry, so the lock is released first.
+    if let Some(other) = code_owner(&map, code, name) {
+        drop(map);
+        throw!(
+            UncategorizedException,
+            &format!("Code {} is already assigned to \"{}\"", code, other)
+        );
+    }
+    match map.get_mut(name) {
+        Some(info) => {
+            info.code = Some(code);
+            info.http_status = Some(http_status);
+            Ok(())
+        }
+        None => {
+            drop(map);
+            throw!(
+                UncategorizedException,
+                &format!("\"{}\" is not a registered exception name", name)
+            );
+        }
+    }
+}
+
+/// The name other than `name` that `code` is assigned to, if any.
+#[cfg(feature = "std")]
+fn code_owner(map: &HashMap<String, NameInfo>, code: u32, name: &str) -> Option<String> {
+    map.iter()
+        .find(|(other, info)| info.code == Some(code) && other.as_str() != name)
+        .map(|(other, _)| other.clone())
+}
+
+/// The numeric code registered for `name`, if any.
+#[cfg(feature = "std")]
+pub fn code_of(name: &str) -> Option<u32> {
+    let map = registry().read().unwrap_or_else(|e| e.into_inner());
+    map.get(name)?.code
+}
+
+/// The HTTP status registered for `name` or, failing that, for its closest category.
+/// Defaults to `DEFAULT_HTTP_STATUS`.
+#[cfg(feature = "std")]
+pub fn http_status_of(name: &str) -> u16 {
+    let map = registry().read().unwrap_or_else(|e| e.into_inner());
+    let mut cur = map.get(name);
+    while let Some(info) = cur {
+        if let Some(status) = info.http_status {
+            return status;
+        }
+        cur = info.parent.as_ref().and_then(|parent| map.get(parent));
+    }
+    DEFAULT_HTTP_STATUS
+}
+
+/// The registered parent of `name`, if any.
+#[cfg(feature = "std")]
+pub fn parent_of(name: &str) -> Option<String> {
+    let map = registry().read().unwrap_or_else(|e| e.into_inner());
+    map.get(name)?.parent.clone()
+}
+
+/// Whether `name` equals `ancestor` or is registered, transitively, under `ancestor`.
+#[cfg(feature = "std")]
+pub fn is_subclass_of(name: &str, ancestor: &str) -> bool {
+    let map = registry().read().unwrap_or_else(|e| e.into_inner());
+    subclass_in(&map, name, ancestor)
+}
+
+#[cfg(feature = "std")]
+fn subclass_in(map: &HashMap<String, NameInfo>, name: &str, ancestor: &str) -> bool {
+    let mut cur = name;
+    loop {
+        if cur == ancestor {
+            return true;
+        }
+        match map.get(cur).and_then(|info| info.parent.as_deref()) {
+            Some(parent) => cur = parent,
+            None => return false,
+        }
+    }
+}
+
+/// Without `std` there is no registry: only the names of `DEFINED_EXCEPTIONS` are known,
+/// with the parent, code, HTTP status, doc and template they are declared with.
+#[cfg(not(feature = "std"))]
+mod builtin {
+    use super::*;
+
+    fn def_of(name: &str) -> Option<&'static ExceptionDef> {
+        DEFINED_EXCEPTIONS.iter().find(|def| def.name == name)
+    }
+
+    /// Every built-in name, sorted.
+    pub fn known_exceptions() -> Vec<String> {
+        let mut names: Vec<String> = DEFINED_EXCEPTIONS
+            .iter()
+            .map(|def| def.name.to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// The doc comment `name` is declared with, if any.
+    pub fn doc_of(name: &str) -> Option<String> {
+        Some(def_of(name)?.get_doc()).filter(|doc| !doc.is_empty())
+    }
+
+    /// The default context of `name`, used by `throw!(name)`.
+    pub fn template_of(name: &str) -> Option<String> {
+        def_of(name)?.template.map(|t| t.to_string())
+    }
+
+    /// The numeric code declared for `name`, if any.
+    pub fn code_of(name: &str) -> Option<u32> {
+        def_of(name)?.code
+    }
+
+    /// The HTTP status declared for `name` or, failing that, for its closest category.
+    /// Defaults to `DEFAULT_HTTP_STATUS`.
+    pub fn http_status_of(name: &str) -> u16 {
+        let mut cur = def_of(name);
+        while let Some(def) = cur {
+            if let Some(status) = def.http_status {
+                return status;
+            }
+            cur = def.parent.and_then(def_of);
+        }
+        DEFAULT_HTTP_STATUS
+    }
+
+    /// The declared parent of `name`, if any.
+    pub fn parent_of(name: &str) -> Option<String> {
+        def_of(name)?.parent.map(|p| p.to_string())
+    }
+
+    /// Whether `name` equals `ancestor` or is declared, transitively, under `ancestor`.
+    pub fn is_subclass_of(name: &str, ancestor: &str) -> bool {
+        let mut cur = name;
+        loop {
+            if cur == ancestor {
+                return true;
+            }
+            match def_of(cur).and_then(|def| def.parent) {
+                Some(parent) => cur = parent,
+                None => return false,
+            }
+        }
+    }
+}
+#[cfg(not(feature = "std"))]
+pub use builtin::*;
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "strict-exception-names")]
+    #[test]
+    fn test_unregistered_name_warning() {
+        assert!(!warn_if_unregistered(IOException));
+        let name = "TestTypoExcepton";
+        assert!(warn_if_unregistered(name));
+        assert!(!warn_if_unregistered(name));
+    }
+
+    #[test]
+    fn test_hierarchy() -> Outcome<()> {
+        assert!(is_subclass_of(HttpPostException, IOException));
+        assert!(is_subclass_of(HttpPostException, UncategorizedException));
+        assert!(is_subclass_of(IOException, IOException));
+        assert!(!is_subclass_of(IOException, HttpPostException));
+        assert!(!is_subclass_of(
+            "NeverRegisteredException",
+            UncategorizedException
+        ));
+
+        register_exception_name("TestTimeoutException", Some(HttpPostException))?;
+        assert!(is_subclass_of("TestTimeoutException", IOException));
+        assert_eq!(
+            parent_of("TestTimeoutException").as_deref(),
+            Some(HttpPostException)
+        );
+        assert!(register_exception_name(IOException, Some("TestTimeoutException")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_concurrent_cycle() {
+        for _ in 0..100 {
+            let threads = [
+                ("TestPingException", "TestPongException"),
+                ("TestPongException", "TestPingException"),
+            ]
+            .map(|(name, parent)| {
+                std::thread::spawn(move || register_exception_name(name, Some(parent)).is_ok())
+            });
+            let registered = threads.map(|t| t.join().unwrap());
+            assert_eq!(registered.iter().filter(|ok| **ok).count(), 1);
+            // undo whichever won, for the next round.
+            register_exception_name("TestPingException", None).unwrap();
+            register_exception_name("TestPongException", None).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_concurrent_codes() {
+        for round in 0..100 {
+            let code = 60000 + round;
+            let threads = ["TestRaceA", "TestRaceB"].map(|prefix| {
+                let name: &'static str = Box::leak(format!("{}{}Exception", prefix, round).into());
+                std::thread::spawn(move || {
+                    let def = ExceptionDef {
+                        name,
+                        parent: None,
+                        code: Some(code),
+                        http_status: None,
+                        doc: &[],
+                        template: None,
+                    };
+                    register_exceptions(&[def]).is_ok()
+                })
+            });
+            let registered = threads.map(|t| t.join().unwrap());
+            assert_eq!(registered.iter().filter(|ok| **ok).count(), 1);
+        }
+    }
+
+    #[test]
+    fn test_codes() -> Outcome<()> {
+        assert_eq!(code_of(IOException), Some(1002));
+        assert_eq!(code_of("NeverRegisteredException"), None);
+        assert_eq!(http_status_of(DeserializationException), 400);
+        assert_eq!(http_status_of(InvalidUTF8BytesException), 400);
+        assert_eq!(http_status_of(HttpPostException), 502);
+        assert_eq!(http_status_of(PathResolutionException), 500);
+        assert_eq!(http_status_of("NeverRegisteredException"), 500);
+
+        register_exception_name("TestQuotaException", Some(HttpPostException))?;
+        assert_eq!(http_status_of("TestQuotaException"), 502);
+        set_exception_code("TestQuotaException", 9429, 429)?;
+        assert_eq!(code_of("TestQuotaException"), Some(9429));
+        assert_eq!(http_status_of("TestQuotaException"), 429);
+        // re-registering keeps the code.
+        register_exception_name("TestQuotaException", Some(IOException))?;
+        assert_eq!(code_of("TestQuotaException"), Some(9429));
+
+        assert!(set_exception_code("TestQuotaException", 1002, 500).is_err());
+        assert!(set_exception_code("NeverRegisteredException", 9999, 500).is_err());
+        Ok(())
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_codes_with_events() {
+        use crate::logging::{set_exception_event_level, EVENT_LEVEL_TEST_LOCK};
+        let _lock = EVENT_LEVEL_TEST_LOCK
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        set_exception_event_level(Some(tracing::Level::ERROR));
+        // A deadlock fails the test instead of hanging it.
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let subscriber = tracing_subscriber::fmt()
+                .with_writer(std::io::sink)
+                .finish();
+            tracing::subscriber::with_default(subscriber, || {
+                let unknown = set_exception_code("NeverRegisteredException", 77777, 500);
+                let taken = set_exception_code(HttpPostException, 1002, 500);
+                let conflict = register_exceptions(&[ExceptionDef {
+                    name: "TestConflictException",
+                    parent: Some(IOException),
+                    code: Some(1002),
+                    http_status: None,
+                    doc: &[],
+                    template: None,
+                }]);
+                let results = (unknown.is_err(), taken.is_err(), conflict.is_err());
+                tx.send(results).unwrap();
+            });
+        });
+        let res = rx.recv_timeout(std::time::Duration::from_secs(10));
+        set_exception_event_level(None);
+        assert_eq!(res.expect("the registry deadlocks"), (true, true, true));
+        // a rejected entry is not registered at all.
+        assert_eq!(parent_of("TestConflictException"), None);
+    }
+
+    mod downstream {
+        use crate::EXN;
+
+        crate::define_exception! {
+            /// The configuration
+            /// is invalid.
+            TestConfigException: EXN::DeserializationException [9501] => "The configuration is invalid";
+            TestConfigKeyException: TestConfigException;
+        }
+
+        crate::define_exception! {
+            table TEST_NET_EXCEPTIONS;
+            TestPeerResetException: EXN::IOException [9502];
+        }
+    }
+
+    #[test]
+    fn test_define_exception() -> Outcome<()> {
+        use downstream::*;
+        assert_eq!(TestConfigKeyException, "TestConfigKeyException");
+        assert_eq!(DEFINED_EXCEPTIONS.len(), 2);
+        register_exceptions(DEFINED_EXCEPTIONS)?;
+        assert_eq!(TEST_NET_EXCEPTIONS.len(), 1);
+        register_exceptions(TEST_NET_EXCEPTIONS)?;
+        assert!(is_subclass_of(TestPeerResetException, IOException));
+        assert!(is_subclass_of(
+            TestConfigKeyException,
+            DeserializationException
+        ));
+        assert_eq!(code_of(TestConfigException), Some(9501));
+        assert_eq!(http_status_of(TestConfigException), 400);
+        assert_eq!(
+            doc_of(TestConfigException).as_deref(),
+            Some("The configuration\nis invalid.")
+        );
+        assert_eq!(doc_of(TestConfigKeyException), None);
+
+        let names = known_exceptions();
+        assert!(names.iter().any(|name| name == DataFormatException));
+        assert!(names.iter().any(|name| name == TestConfigKeyException));
+
+        let res: Outcome<()> = (|| throw!(TestConfigException))();
+        assert_eq!(
+            res.unwrap_err().get_context(),
+            Some("The configuration is invalid")
+        );
+        let res: Outcome<()> = (|| throw!(TestConfigKeyException))();
+        assert_eq!(res.unwrap_err().get_context(), Some(""));
+        Ok(())
+    }
+}
+
+#[cfg(all(test, not(feature = "std")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin() {
+        assert!(is_subclass_of(HttpPostException, IOException));
+        assert!(is_subclass_of(HttpPostException, UncategorizedException));
+        assert!(!is_subclass_of(IOException, HttpPostException));
+        assert!(!is_subclass_of(
+            "UnregisteredException",
+            UncategorizedException
+        ));
+
+        assert_eq!(http_status_of(HttpPostException), 502);
+        assert_eq!(http_status_of(InvalidUTF8BytesException), 400);
+        assert_eq!(http_status_of(IOException), 500);
+        assert_eq!(http_status_of("UnregisteredException"), DEFAULT_HTTP_STATUS);
+        assert_eq!(code_of(IOException), Some(1002));
+
+        assert_eq!(
+            template_of(IOException).as_deref(),
+            Some("An I/O operation fails")
+        );
+        assert_eq!(template_of(DummyException), None);
+        assert_eq!(template_of("UnregisteredException"), None);
+        assert!(known_exceptions().iter().any(|name| name == PanicException));
+    }
+}
@@ -0,0 +1,289 @@
+use crate::*;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::AssertUnwindSafe;
+use std::ptr;
+
+/// Returned by a function of the C interface that succeeds.
+pub const XM_OK: i32 = 0;
+
+/// Returned by a function of the C interface that fails with an exception
+/// whose name has no numeric code. See `exception_names::set_exception_code`.
+pub const XM_ERROR: i32 = -1;
+
+/// An `Exception` handed to C as an opaque pointer.
+/// Obtained from a function of the C interface that fails, and released by `xm_exception_free`.
+pub struct XmException {
+    ex: Box<Exception>,
+}
+
+impl XmException {
+    /// Give up the ownership of `ex` to C.
+    pub fn into_raw(ex: Box<Exception>) -> *mut XmException {
+        Box::into_raw(Box::new(XmException { ex }))
+    }
+
+    /// Take back an exception given up by `into_raw`, e.g. to catch an error returned by C code.
+    ///
+    /// # Safety
+    /// `handle` must come from `into_raw`, and must not be used afterwards.
+    pub unsafe fn from_raw(handle: *mut XmException) -> Box<Exception> {
+        Box::from_raw(handle).ex
+    }
+}
+
+/// The code a function of the C interface returns when it fails with `ex`: the numeric code of its name,
+/// or `XM_ERROR` if it has none, or if that code reads as `XM_OK` or does not fit in `i32`.
+pub fn ffi_error_code(ex: &Exception) -> i32 {
+    match ex.get_code().map(i32::try_from) {
+        Some(Ok(code)) if code != XM_OK => code,
+        _ => XM_ERROR,
+    }
+}
+
+/// Run the body of an `extern "C"` function that reports failure the way the C interface does.
+/// Returns `XM_OK` and sets `*err` to null if `f` succeeds. Otherwise the exception is stored into `*err`,
+/// and its `ffi_error_code` is returned. A panic becomes a `PanicException` instead of unwinding into C.
+/// ```ignore
+/// #[no_mangle]
+/// pub unsafe extern "C" fn load_config(
+///     path: *const c_char,
+///     text: *mut *mut c_char,
+///     err: *mut *mut XmException,
+/// ) -> i32 {
+///     ffi_call(err, || {
+///         let path = charp_to_str(path)?;
+///         *text = str_to_charp(&read_str_from_file(&path)?);
+///         Ok(())
+///     })
+/// }
+/// ```
+///
+/// # Safety
+/// `err` must be null, in which case the exception is dropped, or be valid for reads and writes.
+/// `*err` must be null or an exception left by an earlier call, which is freed first,
+/// so that a caller may reuse one `XmException *` across calls. A freed exception must be reset to null.
+pub unsafe fn ffi_call<F>(err: *mut *mut XmException, f: F) -> i32
+where
+    F: FnOnce() -> Outcome<()>,
+{
+    if !err.is_null() {
+        xm_exception_free(*err);
+        *err = ptr::null_mut();
+    }
+    // Nothing touched by `f` is used once it has panicked.
+    let (code, handle) = match outcome_from_panic(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => (XM_OK, ptr::null_mut()),
+        Ok(Err(ex)) | Err(ex) => (ffi_error_code(&ex), XmException::into_raw(ex)),
+    };
+    if err.is_null() {
+        xm_exception_free(handle);
+    } else {
+        *err = handle;
+    }
+    code
+}
+
+/// Copy `text` into a NUL-terminated string owned by C, which is released by `xm_string_free`.
+/// NUL bytes inside `text` are removed, since a C string cannot hold them.
+pub fn str_to_charp(text: &str) -> *mut c_char {
+    let text = CString::new(text.replace('\0', "")).unwrap_or_default();
+    text.into_raw()
+}
+
+/// Copy a NUL-terminated string received from C into an owned String.
+///
+/// # Safety
+/// `charp` must be null or point to a NUL-terminated string.
+pub unsafe fn charp_to_str(charp: *const c_char) -> Outcome<String> {
+    if charp.is_null() {
+        throw!(EXN::NullPointerException, "The string is null");
+    }
+    let text = CStr::from_ptr(charp)
+        .to_str()
+        .catch(EXN::InvalidUTF8BytesException, "The C string is not UTF-8")?;
+    Ok(text.to_string())
+}
+
+/// The name of the exception. Release it by `xm_string_free`. Null if `ex` is null.
+///
+/// # Safety
+/// `ex` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn xm_exception_name(ex: *const XmException) -> *mut c_char {
+    match ex.as_ref() {
+        Some(handle) => str_to_charp(handle.ex.get_name()),
+        None => ptr::null_mut(),
+    }
+}
+
+/// The context of the outermost frame, redacted like any rendered context.
+/// Release it by `xm_string_free`. Null if `ex` is null or the frame has no context.
+///
+/// # Safety
+/// `ex` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn xm_exception_context(ex: *const XmException) -> *mut c_char {
+    match ex.as_ref().and_then(|handle| handle.ex.get_context()) {
+        Some(ctx) => str_to_charp(&redact_text(ctx)),
+        None => ptr::null_mut(),
+    }
+}
+
+/// The whole chain rendered in `exception_format()`, as by `Display`.
+/// Release it by `xm_string_free`. Null if `ex` is null.
+///
+/// # Safety
+/// `ex` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn xm_exception_trace(ex: *const XmException) -> *mut c_char {
+    match ex.as_ref() {
+        Some(handle) => str_to_charp(&handle.ex.to_string()),
+        None => ptr::null_mut(),
+    }
+}
+
+/// The code returned along with the exception, see `ffi_error_code`. `XM_ERROR` if `ex` is null.
+///
+/// # Safety
+/// `ex` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn xm_exception_code(ex: *const XmException) -> i32 {
+    match ex.as_ref() {
+        Some(handle) => ffi_error_code(&handle.ex),
+        None => XM_ERROR,
+    }
+}
+
+/// Release an exception. Does nothing if `ex` is null.
+///
+/// # Safety
+/// `ex` must be null or a live handle, which must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn xm_exception_free(ex: *mut XmException) {
+    if !ex.is_null() {
+        drop(XmException::from_raw(ex));
+    }
+}
+
+/// Release a string returned by the C interface. Does nothing if `text` is null.
+///
+/// # Safety
+/// `text` must be null or a string returned by the C interface, which must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn xm_string_free(text: *mut c_char) {
+    if !text.is_null() {
+        drop(CString::from_raw(text));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn take_string(text: *mut c_char) -> Option<String> {
+        unsafe {
+            let owned = charp_to_str(text).ok();
+            xm_string_free(text);
+            owned
+        }
+    }
+
+    #[test]
+    fn test_ffi_call() {
+        let mut err = ptr::null_mut();
+        let code = unsafe { ffi_call(&mut err, || Ok(())) };
+        assert_eq!(code, XM_OK);
+        assert!(err.is_null());
+
+        let code = unsafe {
+            ffi_call(&mut err, || {
+                read_str_from_file("!!$%!$>TXT").catch("FfiTestException", "password=hunter2")?;
+                Ok(())
+            })
+        };
+        assert_eq!(code, XM_ERROR);
+        unsafe {
+            assert_eq!(xm_exception_code(err), XM_ERROR);
+            assert_eq!(
+                take_string(xm_exception_name(err)).unwrap(),
+                "FfiTestException"
+            );
+            let ctx = take_string(xm_exception_context(err)).unwrap();
+            assert_eq!(ctx, "password=***");
+            let trace = take_string(xm_exception_trace(err)).unwrap();
+            assert!(trace.contains("IOException"));
+            let ex = XmException::from_raw(err);
+            assert!(ex.root_cause().is::<std::io::Error>());
+        }
+        err = ptr::null_mut();
+
+        let code = unsafe { ffi_call(&mut err, || panic!("boom")) };
+        assert_eq!(code, 1010);
+        unsafe {
+            assert_eq!(take_string(xm_exception_context(err)).unwrap(), "boom");
+        }
+
+        // the exception left by the panic is freed before `err` is reused.
+        let code = unsafe { ffi_call(&mut err, || throw!(EXN::IOException, "")) };
+        assert_eq!(code, 1002);
+        let code = unsafe { ffi_call(&mut err, || Ok(())) };
+        assert_eq!(code, XM_OK);
+        assert!(err.is_null());
+    }
+
+    /// Generate the C header of this file, and compare it with the copy checked in as `include/xuanmi_base_support.h`.
+    /// Run with `XUANMI_UPDATE_HEADER=1` to update the checked-in copy.
+    #[test]
+    fn test_header_is_up_to_date() {
+        let crate_dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+        let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml")).unwrap();
+        let mut generated = Vec::new();
+        cbindgen::Builder::new()
+            .with_config(config)
+            .with_src(crate_dir.join("src/ffi.rs"))
+            .generate()
+            .expect("Cannot generate the C header from src/ffi.rs")
+            .write(&mut generated);
+        let generated = String::from_utf8(generated).unwrap();
+        let path = crate_dir.join("include/xuanmi_base_support.h");
+        let path = path.to_str().unwrap();
+        if std::env::var_os("XUANMI_UPDATE_HEADER").is_some() {
+            std::fs::write(path, &generated).unwrap();
+        }
+        let checked_in = std::fs::read_to_string(path).unwrap_or_default();
+        assert!(
+            checked_in == generated,
+            "{} is outdated, run `XUANMI_UPDATE_HEADER=1 cargo test --features ffi`",
+            path
+        );
+    }
+
+    #[test]
+    fn test_error_codes() -> Outcome<()> {
+        EXN::register_exception_name("FfiZeroCodeException", None)?;
+        EXN::set_exception_code("FfiZeroCodeException", 0, 500)?;
+        EXN::register_exception_name("FfiHugeCodeException", None)?;
+        EXN::set_exception_code("FfiHugeCodeException", u32::MAX, 500)?;
+        for name in ["FfiZeroCodeException", "FfiHugeCodeException"] {
+            let code = unsafe { ffi_call(ptr::null_mut(), || throw!(name, "")) };
+            assert_eq!(code, XM_ERROR);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_strings() {
+        assert_eq!(take_string(str_to_charp("a\0b")).unwrap(), "ab");
+        unsafe {
+            let ex = charp_to_str(ptr::null()).unwrap_err();
+            assert_eq!(ex.get_name(), EXN::NullPointerException);
+            let bytes = b"\xff\0";
+            let ex = charp_to_str(bytes.as_ptr() as *const c_char).unwrap_err();
+            assert_eq!(ex.get_name(), EXN::InvalidUTF8BytesException);
+            assert!(xm_exception_name(ptr::null()).is_null());
+            assert_eq!(xm_exception_code(ptr::null()), XM_ERROR);
+            xm_exception_free(ptr::null_mut());
+            xm_string_free(ptr::null_mut());
+        }
+    }
+}
@@ -2,6 +2,7 @@ use crate::EXN::*;
 use crate::*;
 use std::fs::File;
 use std::io::{Read, Seek, SeekFrom, Write};
+#[cfg(feature = "paths")]
 use std::path::{self, Path, PathBuf};
 use std::{str, string::String};
 
@@ -53,10 +54,12 @@ pub fn write_str_to_file(path: &str, text: &str) -> Outcome<usize> {
     Ok(len_to_write)
 }
 
+#[cfg(feature = "paths")]
 pub trait LexicalAbspath {
     fn to_lexical_abspath(&self) -> Outcome<String>;
 }
 
+#[cfg(feature = "paths")]
 impl<STR> LexicalAbspath for STR
 where
     STR: AsRef<str> + core::fmt::Display,
@@ -90,7 +93,7 @@ where
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "paths"))]
 mod tests {
     use crate::{LexicalAbspath, Outcome};
 
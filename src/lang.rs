@@ -12,7 +12,7 @@ macro_rules! fnn {
     () => {{
         fn f() {}
         fn type_name_of<T>(_: T) -> &'static str {
-            std::any::type_name::<T>()
+            ::core::any::type_name::<T>()
         }
         let name = type_name_of(f);
         // `3` is the length of the `::f`.
@@ -21,7 +21,7 @@ macro_rules! fnn {
 }
 
 pub fn typename<T>(_obj: T) -> &'static str {
-    return std::any::type_name::<T>();
+    return core::any::type_name::<T>();
 }
 
-pub const PTRLEN: usize = std::mem::size_of::<usize>();
+pub const PTRLEN: usize = core::mem::size_of::<usize>();
@@ -1,25 +1,94 @@
 // #![feature(track_caller)] // this has been a stable feature since Rust 1.46.0
+#![cfg_attr(not(feature = "std"), no_std)]
 #![allow(non_snake_case)]
 #![allow(non_upper_case_globals)]
 #![allow(unused_assignments)]
 
+#[cfg_attr(not(feature = "std"), macro_use)]
+extern crate alloc;
+
+/// What the std prelude would bring into the modules that also build without `std`.
+#[cfg(not(feature = "std"))]
+mod no_std_prelude {
+    pub use alloc::boxed::Box;
+    pub use alloc::string::{String, ToString};
+    pub use alloc::vec::Vec;
+}
+
+/// Used by the exported macros, so that they also expand in `no_std` crates.
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::string::ToString;
+    pub use alloc::{format, vec};
+}
+
 #[macro_use]
 mod lang;
 pub use lang::*;
-pub mod logging;
+#[cfg(feature = "std")]
 mod conversion;
+#[cfg(feature = "tracing")]
+pub mod logging;
+#[cfg(feature = "std")]
 pub use conversion::*;
+#[cfg(feature = "std")]
 mod io;
+#[cfg(feature = "std")]
 pub use io::*;
 #[macro_use]
 mod exception;
 pub use exception::*;
+mod exception_format;
+pub use exception_format::*;
+#[cfg(feature = "std")]
+mod metadata;
+#[cfg(feature = "std")]
+pub use metadata::*;
+mod async_outcome;
+pub use async_outcome::*;
+#[cfg(feature = "std")]
+mod panic_hook;
+#[cfg(feature = "std")]
+pub use panic_hook::*;
+mod multi_exception;
+pub use multi_exception::*;
+#[cfg(feature = "std")]
+mod retry;
+#[cfg(feature = "std")]
+pub use retry::*;
+#[cfg(feature = "std")]
+mod termination;
+#[cfg(feature = "std")]
+pub use termination::*;
+#[cfg(feature = "std")]
+mod redaction;
+#[cfg(feature = "std")]
+pub use redaction::*;
+#[cfg(feature = "std")]
+mod reporter;
+#[cfg(feature = "std")]
+pub use reporter::*;
+#[cfg(feature = "std")]
+#[macro_use]
+mod assertions;
+#[cfg(feature = "std")]
+pub use assertions::*;
 pub mod exception_names;
 pub use exception_names as EXN;
+#[cfg(feature = "ffi")]
+mod ffi;
+#[cfg(feature = "idgen")]
 pub mod idgen;
+#[cfg(feature = "ffi")]
+pub use ffi::*;
+#[cfg(feature = "std")]
 mod qsort;
+#[cfg(feature = "std")]
 pub use qsort::*;
 
+#[cfg(feature = "tracing")]
 pub use tracing;
+#[cfg(feature = "tracing")]
 pub use tracing_appender;
+#[cfg(feature = "tracing")]
 pub use tracing_subscriber;
@@ -1,3 +1,14 @@
+use crate::{Exception, Fields};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::{LookupSpan, Registry};
+
 // 2023-08-14 14:19 (GMT+8)
 // I don't exactly know why, but if one implement init_tracer as a function,
 // the tracer will not be initialized. This phenomenon is repeatable.
@@ -24,6 +35,377 @@ macro_rules! init_tracer {
             .compact()
             .pretty()
             .finish();
+        // lets exceptions capture the field values of the active spans.
+        let subscriber = xuanmi_base_support::tracing_subscriber::layer::SubscriberExt::with(
+            subscriber,
+            xuanmi_base_support::logging::SpanFieldsLayer,
+        );
         xuanmi_base_support::tracing::subscriber::set_global_default(subscriber).unwrap();
     };
-}
\ No newline at end of file
+}
+
+/// 0: disabled; otherwise 1 + index into `LEVELS`.
+static EXCEPTION_EVENT_LEVEL: AtomicU8 = AtomicU8::new(0);
+static SPAN_CAPTURE: AtomicBool = AtomicBool::new(false);
+const LEVELS: [Level; 5] = [
+    Level::TRACE,
+    Level::DEBUG,
+    Level::INFO,
+    Level::WARN,
+    Level::ERROR,
+];
+
+/// Emit a `tracing` event at `level` whenever an exception frame is created or propagated.
+/// `None` turns it off, which is the default.
+pub fn set_exception_event_level(level: Option<Level>) {
+    let val = match level {
+        Some(level) => 1 + LEVELS.iter().position(|l| *l == level).unwrap_or(0) as u8,
+        None => 0,
+    };
+    EXCEPTION_EVENT_LEVEL.store(val, Ordering::Relaxed);
+}
+
+/// Serializes the tests that change `set_exception_event_level`.
+#[cfg(test)]
+pub(crate) static EVENT_LEVEL_TEST_LOCK: Mutex<()> = Mutex::new(());
+
+pub fn exception_event_level() -> Option<Level> {
+    match EXCEPTION_EVENT_LEVEL.load(Ordering::Relaxed) {
+        0 => None,
+        val => Some(LEVELS[val as usize - 1]),
+    }
+}
+
+/// Let `Exception::new` record the active span and its ancestors. Disabled by default.
+/// Field values are only available if the subscriber has a `SpanFieldsLayer`,
+/// which `init_tracer!` installs.
+pub fn set_span_capture_enabled(enabled: bool) {
+    SPAN_CAPTURE.store(enabled, Ordering::Relaxed);
+}
+
+pub fn span_capture_enabled() -> bool {
+    SPAN_CAPTURE.load(Ordering::Relaxed)
+}
+
+/// Suppresses repeated logging of the same fingerprint within a window.
+struct RateLimiter {
+    window: Option<Duration>,
+    /// When each fingerprint was last logged, and how many times it was suppressed since.
+    seen: HashMap<u64, (Instant, u64)>,
+}
+
+/// Fingerprints beyond this count are forgotten once their window has passed.
+const RATE_LIMITER_CAPACITY: usize = 4096;
+
+impl RateLimiter {
+    fn check(&mut self, fingerprint: u64, now: Instant) -> Option<u64> {
+        let window = match self.window {
+            Some(window) => window,
+            None => return Some(0),
+        };
+        if let Some((last, suppressed)) = self.seen.get_mut(&fingerprint) {
+            if now.duration_since(*last) < window {
+                *suppressed += 1;
+                return None;
+            }
+            let count = *suppressed;
+            *last = now;
+            *suppressed = 0;
+            return Some(count);
+        }
+        if self.seen.len() >= RATE_LIMITER_CAPACITY {
+            self.seen
+                .retain(|_, (last, _)| now.duration_since(*last) < window);
+        }
+        self.seen.insert(fingerprint, (now, 0));
+        Some(0)
+    }
+}
+
+fn rate_limiter() -> &'static Mutex<RateLimiter> {
+    static LIMITER: OnceLock<Mutex<RateLimiter>> = OnceLock::new();
+    LIMITER.get_or_init(|| {
+        Mutex::new(RateLimiter {
+            window: None,
+            seen: HashMap::new(),
+        })
+    })
+}
+
+/// Log an exception with a given `Exception::fingerprint` at most once per `window`.
+/// Applies to the events of `set_exception_event_level` and of `install_panic_hook`.
+/// `None` turns it off, which is the default. Changing it forgets every fingerprint.
+pub fn set_exception_rate_limit(window: Option<Duration>) {
+    let mut limiter = rate_limiter().lock().unwrap_or_else(|e| e.into_inner());
+    limiter.window = window;
+    limiter.seen.clear();
+}
+
+pub fn exception_rate_limit() -> Option<Duration> {
+    let limiter = rate_limiter().lock().unwrap_or_else(|e| e.into_inner());
+    limiter.window
+}
+
+/// `None` if an exception with `fingerprint` should not be logged now;
+/// otherwise the number of times it was suppressed since it was last logged.
+pub fn check_exception_rate_limit(fingerprint: u64) -> Option<u64> {
+    let mut limiter = rate_limiter().lock().unwrap_or_else(|e| e.into_inner());
+    limiter.check(fingerprint, Instant::now())
+}
+
+/// A span that was active when an exception frame was created.
+#[derive(Clone, PartialEq, Debug)]
+pub struct SpanInfo {
+    pub name: String,
+    pub fields: Fields,
+}
+
+/// Records the field values of every span into its extensions,
+/// so that they can be read back when an exception is created.
+pub struct SpanFieldsLayer;
+
+struct SpanFields(Fields);
+
+impl SpanFields {
+    /// Like `Exception::set_field`, a value recorded again replaces the previous one.
+    fn set(&mut self, field: &Field, value: serde_json::Value) {
+        match self.0.iter_mut().find(|(k, _)| k == field.name()) {
+            Some((_, v)) => *v = value,
+            None => self.0.push((field.name().to_string(), value)),
+        }
+    }
+}
+
+impl Visit for SpanFields {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.set(field, value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.set(field, value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.set(field, value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.set(field, value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.set(field, format!("{:?}", value).into());
+    }
+}
+
+impl<S> Layer<S> for SpanFieldsLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            let mut fields = SpanFields(Fields::new());
+            attrs.record(&mut fields);
+            span.extensions_mut().insert(fields);
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            let mut exts = span.extensions_mut();
+            if let Some(fields) = exts.get_mut::<SpanFields>() {
+                values.record(fields);
+            }
+        }
+    }
+}
+
+/// The active span and its ancestors, outermost first.
+pub(crate) fn current_spans() -> Vec<SpanInfo> {
+    tracing::dispatcher::get_default(|dispatch| {
+        let registry = match dispatch.downcast_ref::<Registry>() {
+            Some(registry) => registry,
+            None => return Vec::new(),
+        };
+        let current = dispatch.current_span();
+        let span = match current.id().and_then(|id| registry.span(id)) {
+            Some(span) => span,
+            None => return Vec::new(),
+        };
+        span.scope()
+            .from_root()
+            .map(|span| SpanInfo {
+                name: span.name().to_string(),
+                fields: match span.extensions().get::<SpanFields>() {
+                    Some(fields) => fields.0.clone(),
+                    None => Fields::new(),
+                },
+            })
+            .collect()
+    })
+}
+
+/// Emit the event configured by `set_exception_event_level` for a single frame.
+pub(crate) fn emit_exception_event(ex: &Exception) {
+    let level = match exception_event_level() {
+        Some(level) => level,
+        None => return,
+    };
+    let propagated = match ex.get_caused_by() {
+        Some(frame) => frame.is::<Exception>(),
+        None => false,
+    };
+    let fingerprint = ex.fingerprint();
+    let suppressed = match check_exception_rate_limit(fingerprint) {
+        Some(suppressed) => suppressed,
+        None => return,
+    };
+    let fingerprint = format!("{:016x}", fingerprint);
+    let fields = crate::exception_format::fields_text(ex.get_fields());
+    macro_rules! emit {
+        ($level:expr) => {
+            tracing::event!(
+                $level,
+                exception = ex.get_name(),
+                code = ex.get_code(),
+                location = %format!("{}:{}:{}", ex.get_file(), ex.get_line(), ex.get_column()),
+                context = %crate::redact_text(ex.get_context().unwrap_or("")),
+                fields = %fields,
+                propagated,
+                fingerprint = %fingerprint,
+                suppressed,
+                "{}",
+                if propagated { "exception propagated" } else { "exception created" }
+            )
+        };
+    }
+    match level {
+        Level::TRACE => emit!(Level::TRACE),
+        Level::DEBUG => emit!(Level::DEBUG),
+        Level::INFO => emit!(Level::INFO),
+        Level::WARN => emit!(Level::WARN),
+        Level::ERROR => emit!(Level::ERROR),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    struct EventCollector(Arc<Mutex<Vec<String>>>);
+
+    struct MessageVisitor(String);
+
+    impl Visit for MessageVisitor {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            if field.name() == "message" {
+                self.0 = format!("{:?}", value);
+            }
+        }
+    }
+
+    impl<S: Subscriber> Layer<S> for EventCollector {
+        fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+            let mut visitor = MessageVisitor(String::new());
+            event.record(&mut visitor);
+            let line = format!("{} {}", event.metadata().level(), visitor.0);
+            self.0.lock().unwrap().push(line);
+        }
+    }
+
+    fn inner() -> Outcome<()> {
+        throw!(EXN::IOException, "Disk is full");
+    }
+
+    fn outer() -> Outcome<()> {
+        inner().catch_()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_exception_tracing() {
+        let _lock = EVENT_LEVEL_TEST_LOCK
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let subscriber = Registry::default()
+            .with(SpanFieldsLayer)
+            .with(EventCollector(events.clone()));
+        set_span_capture_enabled(true);
+        set_exception_event_level(Some(Level::WARN));
+        let ex = tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("request", id = 42_u64, user = "alice");
+            let _guard = span.enter();
+            outer().unwrap_err()
+        });
+        set_span_capture_enabled(false);
+        set_exception_event_level(None);
+
+        assert!(ex.get_spans().is_empty());
+        let inner_ex = ex.get_caused_by().unwrap().as_exception().unwrap();
+        let expected = SpanInfo {
+            name: "request".to_string(),
+            fields: vec![
+                ("id".to_string(), 42.into()),
+                ("user".to_string(), "alice".into()),
+            ],
+        };
+        assert_eq!(inner_ex.get_spans(), [expected]);
+        assert!(ex
+            .to_string()
+            .contains("\nSpan: request{id=42, user=\"alice\"}\n"));
+        assert_eq!(
+            *events.lock().unwrap(),
+            ["WARN exception created", "WARN exception propagated"]
+        );
+    }
+
+    #[test]
+    fn test_span_record_replaces() {
+        let _lock = EVENT_LEVEL_TEST_LOCK
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let subscriber = Registry::default().with(SpanFieldsLayer);
+        set_span_capture_enabled(true);
+        let ex = tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("retry", attempt = 1_u64, peer = tracing::field::Empty);
+            let _guard = span.enter();
+            span.record("attempt", 2_u64);
+            span.record("peer", "10.0.0.1");
+            span.record("attempt", 3_u64);
+            inner().unwrap_err()
+        });
+        set_span_capture_enabled(false);
+
+        let expected = SpanInfo {
+            name: "retry".to_string(),
+            fields: vec![
+                ("attempt".to_string(), 3.into()),
+                ("peer".to_string(), "10.0.0.1".into()),
+            ],
+        };
+        assert_eq!(ex.get_spans(), [expected]);
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let mut limiter = RateLimiter {
+            window: None,
+            seen: HashMap::new(),
+        };
+        let t0 = Instant::now();
+        assert_eq!(limiter.check(1, t0), Some(0));
+        assert_eq!(limiter.check(1, t0), Some(0));
+
+        limiter.window = Some(Duration::from_secs(10));
+        assert_eq!(limiter.check(1, t0), Some(0));
+        assert_eq!(limiter.check(1, t0 + Duration::from_secs(1)), None);
+        assert_eq!(limiter.check(2, t0 + Duration::from_secs(2)), Some(0));
+        assert_eq!(limiter.check(1, t0 + Duration::from_secs(9)), None);
+        assert_eq!(limiter.check(1, t0 + Duration::from_secs(10)), Some(2));
+        assert_eq!(limiter.check(1, t0 + Duration::from_secs(11)), None);
+    }
+}
@@ -1,7 +1,7 @@
 extern crate xuanmi_base_support;
 use xuanmi_base_support::*;
 
-fn main() {
+fn main() -> std::process::ExitCode {
     fn actual_test() -> Outcome<()> {
         use std::fs::File;
         let path = "!!$%!$>TXT";
@@ -16,6 +16,5 @@ fn main() {
         let _x = actual_test().catch("AnotherIntendedException", "")?;
         Ok(())
     }
-    let x = actual_test2();
-    println!("{:#?}", x);
+    run_main(actual_test2)
 }
@@ -0,0 +1,180 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+static METADATA_CAPTURE: AtomicBool = AtomicBool::new(false);
+/// Seconds east of UTC.
+static UTC_OFFSET_SECS: AtomicI32 = AtomicI32::new(8 * 3600);
+
+/// Let `Exception::new` record an `ExceptionMetadata`. Disabled by default.
+/// Only the innermost `Exception` frame of a chain keeps it.
+pub fn set_metadata_capture_enabled(enabled: bool) {
+    METADATA_CAPTURE.store(enabled, Ordering::Relaxed);
+}
+
+pub fn metadata_capture_enabled() -> bool {
+    METADATA_CAPTURE.load(Ordering::Relaxed)
+}
+
+/// Set the timezone in which timestamps are shown, as an offset from UTC in seconds.
+/// Defaults to GMT+8, i.e. `8 * 3600`.
+pub fn set_timestamp_utc_offset(secs: i32) {
+    UTC_OFFSET_SECS.store(secs, Ordering::Relaxed);
+}
+
+pub fn timestamp_utc_offset() -> i32 {
+    UTC_OFFSET_SECS.load(Ordering::Relaxed)
+}
+
+/// Where and when the innermost frame of an exception was created.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct ExceptionMetadata {
+    unix_ms: u64,
+    thread_id: u64,
+    thread_name: Option<String>,
+    pid: u32,
+}
+
+impl ExceptionMetadata {
+    /// The metadata of the current thread and process, now.
+    pub fn capture() -> Self {
+        let unix_ms = match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(elapsed) => elapsed.as_millis() as u64,
+            Err(_) => 0,
+        };
+        let thread = std::thread::current();
+        // `ThreadId::as_u64` is unstable; its `Debug` reads "ThreadId(N)".
+        let thread_id = format!("{:?}", thread.id());
+        let thread_id: String = thread_id.chars().filter(|c| c.is_ascii_digit()).collect();
+        ExceptionMetadata {
+            unix_ms,
+            thread_id: thread_id.parse().unwrap_or(0),
+            thread_name: thread.name().map(|name| name.to_string()),
+            pid: std::process::id(),
+        }
+    }
+
+    #[inline]
+    pub fn get_timestamp(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(self.unix_ms)
+    }
+
+    #[inline]
+    pub fn get_thread_id(&self) -> u64 {
+        self.thread_id
+    }
+
+    #[inline]
+    pub fn get_thread_name(&self) -> Option<&str> {
+        self.thread_name.as_deref()
+    }
+
+    #[inline]
+    pub fn get_pid(&self) -> u32 {
+        self.pid
+    }
+
+    /// The timestamp in the timezone of `set_timestamp_utc_offset`,
+    /// e.g. "2023-08-14 14:19:03.042 (GMT+8)".
+    pub fn timestamp_text(&self) -> String {
+        format_timestamp(self.unix_ms, timestamp_utc_offset())
+    }
+}
+
+/// e.g. `thread "main" (id 1), pid 4242, at 2023-08-14 14:19:03.042 (GMT+8)`.
+impl fmt::Display for ExceptionMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "thread \"{}\" (id {}), pid {}, at {}",
+            self.get_thread_name().unwrap_or("<unnamed>"),
+            self.thread_id,
+            self.pid,
+            self.timestamp_text()
+        )
+    }
+}
+
+fn format_timestamp(unix_ms: u64, offset_secs: i32) -> String {
+    let local_ms = unix_ms as i64 + offset_secs as i64 * 1000;
+    let (days, ms_of_day) = (
+        local_ms.div_euclid(86_400_000),
+        local_ms.rem_euclid(86_400_000),
+    );
+    let (year, month, day) = civil_from_days(days);
+    let secs = ms_of_day / 1000;
+    let mut zone = String::from("GMT");
+    if offset_secs != 0 {
+        let sign = if offset_secs < 0 { '-' } else { '+' };
+        let offset = offset_secs.unsigned_abs();
+        zone += &format!("{}{}", sign, offset / 3600);
+        if !offset.is_multiple_of(3600) {
+            zone += &format!(":{:02}", offset % 3600 / 60);
+        }
+    }
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:03} ({})",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60,
+        ms_of_day % 1000,
+        zone
+    )
+}
+
+/// The proleptic Gregorian date of the `days`-th day since 1970-01-01,
+/// after http://howardhinnant.github.io/date_algorithms.html
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0, 0), "1970-01-01 00:00:00.000 (GMT)");
+        assert_eq!(
+            format_timestamp(1_692_000_000_042, 8 * 3600),
+            "2023-08-14 16:00:00.042 (GMT+8)"
+        );
+        assert_eq!(
+            format_timestamp(1_709_164_800_000, -(5 * 3600 + 1800)),
+            "2024-02-28 18:30:00.000 (GMT-5:30)"
+        );
+        assert_eq!(
+            format_timestamp(951_782_400_000, 0),
+            "2000-02-29 00:00:00.000 (GMT)"
+        );
+    }
+
+    #[test]
+    fn test_capture() {
+        let meta = std::thread::Builder::new()
+            .name("worker-7".to_string())
+            .spawn(ExceptionMetadata::capture)
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(meta.get_thread_name(), Some("worker-7"));
+        assert_eq!(meta.get_pid(), std::process::id());
+        assert!(meta.get_thread_id() > 0);
+        let elapsed = SystemTime::now().duration_since(meta.get_timestamp());
+        assert!(elapsed.unwrap() < Duration::from_secs(60));
+        assert!(meta.to_string().starts_with("thread \"worker-7\" (id "));
+    }
+}
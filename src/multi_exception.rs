@@ -0,0 +1,160 @@
+#[cfg(not(feature = "std"))]
+use crate::no_std_prelude::*;
+use crate::*;
+use core::fmt;
+use core::panic::Location;
+
+/// A list of exceptions reported together, e.g. every invalid entry of a config,
+/// or every failed job of a batch.
+/// It is thrown as the cause of an exception named `MultiException`,
+/// hence reachable by `ex.root_cause().downcast_ref::<MultiException>()`.
+#[derive(Default)]
+pub struct MultiException {
+    children: Vec<Exception>,
+}
+
+impl MultiException {
+    pub fn new() -> Self {
+        MultiException {
+            children: Vec::new(),
+        }
+    }
+
+    /// Takes a `Box` because that is what an `Outcome` carries.
+    #[inline]
+    #[allow(clippy::boxed_local)]
+    pub fn push(&mut self, ex: Box<Exception>) -> &mut Self {
+        self.children.push(*ex);
+        self
+    }
+
+    /// Keep the error of `res`, if any, and hand back its value.
+    pub fn collect<T>(&mut self, res: Outcome<T>) -> Option<T> {
+        match res {
+            Ok(v) => Some(v),
+            Err(ex) => {
+                self.push(ex);
+                None
+            }
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.children.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    #[inline]
+    pub fn get_children(&self) -> &[Exception] {
+        &self.children
+    }
+
+    /// `Ok(())` if no exception is collected.
+    /// Otherwise a `MultiException` frame located at the caller, with `ctx` as its context.
+    #[track_caller]
+    pub fn into_outcome(self, ctx: &str) -> Outcome<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let loc = Location::caller();
+        let mut ex = Exception::new();
+        ex.set_name(EXN::MultiException)
+            .set_file(loc.file())
+            .set_line(loc.line())
+            .set_column(loc.column())
+            .set_context(ctx)
+            .set_caused_by(self);
+        ex.emit_event();
+        Err(ex)
+    }
+}
+
+impl From<Vec<Exception>> for MultiException {
+    fn from(children: Vec<Exception>) -> Self {
+        MultiException { children }
+    }
+}
+
+/// Every child is rendered as an indented subtree, in `ExceptionFormat::Classic`.
+impl fmt::Display for MultiException {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", crate::exception_format::render_multi_classic(self))
+    }
+}
+
+/// Like `iter.collect::<Outcome<Vec<T>>>()`, but gathers every error instead of stopping at the first.
+#[track_caller]
+pub fn collect_outcomes<T, I>(iter: I) -> Outcome<Vec<T>>
+where
+    I: IntoIterator<Item = Outcome<T>>,
+{
+    let loc = Location::caller();
+    let mut errs = MultiException::new();
+    let mut vals = Vec::new();
+    for res in iter {
+        if let Some(v) = errs.collect(res) {
+            vals.push(v);
+        }
+    }
+    if errs.is_empty() {
+        return Ok(vals);
+    }
+    let mut ex = Exception::new();
+    ex.set_name(EXN::MultiException)
+        .set_file(loc.file())
+        .set_line(loc.line())
+        .set_column(loc.column())
+        .set_caused_by(errs);
+    ex.emit_event();
+    Err(ex)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::*;
+
+    fn parse_port(text: &str) -> Outcome<u16> {
+        let port = text.parse::<u16>().catch(
+            EXN::DeserializationException,
+            &format!("Invalid port \"{}\"", text),
+        )?;
+        Ok(port)
+    }
+
+    #[test]
+    fn test_collect_outcomes() {
+        let ports = collect_outcomes(["80", "443"].iter().map(|t| parse_port(t)));
+        assert_eq!(ports.unwrap(), [80, 443]);
+
+        let ex =
+            collect_outcomes(["80", "http", "99999"].iter().map(|t| parse_port(t))).unwrap_err();
+        assert_eq!(ex.get_name(), EXN::MultiException);
+        let multi = ex.root_cause().downcast_ref::<MultiException>().unwrap();
+        assert_eq!(multi.len(), 2);
+        let text = ex.to_string();
+        assert!(text.contains("\n2 exceptions occur:\n[0] Exception \"DeserializationException\""));
+        assert!(text.contains("\n    Context: Invalid port \"http\"\n"));
+        assert!(text.contains("\n[1] Exception \"DeserializationException\""));
+    }
+
+    #[test]
+    fn test_multi_exception_serde() {
+        let mut errs = MultiException::new();
+        errs.collect(parse_port("http"));
+        errs.collect(parse_port("-1"));
+        let ex = errs.into_outcome("Invalid config").unwrap_err();
+        let json = obj_to_json(&ex).unwrap();
+        let ex2: Box<Exception> = json_to_obj(&json).unwrap();
+        let multi = ex2.root_cause().downcast_ref::<MultiException>().unwrap();
+        assert_eq!(
+            multi.get_children()[1].get_context(),
+            Some("Invalid port \"-1\"")
+        );
+        assert_eq!(ex.to_string(), ex2.to_string());
+    }
+}
@@ -0,0 +1,149 @@
+use crate::*;
+use std::any::Any;
+use std::cell::RefCell;
+use std::panic::{Location, PanicHookInfo, UnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+type PanicHook = Box<dyn Fn(&PanicHookInfo<'_>) + Sync + Send + 'static>;
+
+static HOOK_INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// The hook replaced by `install_panic_hook`, put back by `uninstall_panic_hook`.
+static PREVIOUS_HOOK: Mutex<Option<PanicHook>> = Mutex::new(None);
+
+thread_local! {
+    /// Filled by the hook of `install_panic_hook`, consumed by `outcome_from_panic`.
+    static LAST_PANIC: RefCell<Option<(String, u32, u32)>> = const { RefCell::new(None) };
+}
+
+fn payload_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    }
+}
+
+/// Run `f`, turning an unwinding panic into a `PanicException` whose context is the panic message.
+/// The exception is located where the panic occurred if `install_panic_hook` has been called,
+/// otherwise where `outcome_from_panic` is called.
+#[track_caller]
+pub fn outcome_from_panic<T, F>(f: F) -> Outcome<T>
+where
+    F: FnOnce() -> T + UnwindSafe,
+{
+    let caller = Location::caller();
+    LAST_PANIC.with(|last| last.borrow_mut().take());
+    match std::panic::catch_unwind(f) {
+        Ok(v) => Ok(v),
+        Err(payload) => {
+            let (file, line, column) = match LAST_PANIC.with(|last| last.borrow_mut().take()) {
+                Some(loc) => loc,
+                None => (caller.file().to_string(), caller.line(), caller.column()),
+            };
+            let mut ex = Exception::new();
+            ex.set_name(EXN::PanicException)
+                .set_file(&file)
+                .set_line(line)
+                .set_column(column)
+                .set_context(&payload_message(payload.as_ref()))
+                .capture_backtrace_if_enabled();
+            ex.emit_event();
+            Err(ex)
+        }
+    }
+}
+
+/// Whether `install_panic_hook` has been called, hence panics are already reported.
+pub(crate) fn panic_hook_installed() -> bool {
+    HOOK_INSTALLED.load(Ordering::Relaxed)
+}
+
+/// Replace the panic hook with one that formats a panic as a `PanicException`,
+/// prints it to stderr, logs it at ERROR level through `tracing`,
+/// e.g. into the log files set up by `init_tracer!`, and passes it to `report`.
+/// The replaced hook, e.g. of a crash reporter, is kept and put back by `uninstall_panic_hook`.
+pub fn install_panic_hook() {
+    let mut previous = PREVIOUS_HOOK.lock().unwrap_or_else(|e| e.into_inner());
+    let hook = std::panic::take_hook();
+    // Installed twice, `hook` is the one of an earlier call.
+    if !HOOK_INSTALLED.swap(true, Ordering::Relaxed) {
+        *previous = Some(hook);
+    }
+    std::panic::set_hook(Box::new(|info: &PanicHookInfo<'_>| {
+        let mut ex = Exception::new();
+        ex.set_name(EXN::PanicException)
+            .set_context(&payload_message(info.payload()))
+            .capture_backtrace_if_enabled();
+        if let Some(loc) = info.location() {
+            ex.set_file(loc.file())
+                .set_line(loc.line())
+                .set_column(loc.column());
+            let loc = (loc.file().to_string(), loc.line(), loc.column());
+            LAST_PANIC.with(|last| *last.borrow_mut() = Some(loc));
+        }
+        let thread = std::thread::current();
+        let thread = thread.name().unwrap_or("<unnamed>");
+        eprintln!("thread '{}' panicked:\n{}", thread, ex);
+        #[cfg(feature = "tracing")]
+        if let Some(suppressed) = crate::logging::check_exception_rate_limit(ex.fingerprint()) {
+            let fingerprint = format!("{:016x}", ex.fingerprint());
+            tracing::error!(thread, fingerprint = %fingerprint, suppressed, "{}", ex);
+        }
+        report(&ex);
+    }));
+}
+
+/// Put back the panic hook replaced by `install_panic_hook`, undoing it,
+/// so that `run_main` reports a panic by itself again. Does nothing if the hook is not installed.
+pub fn uninstall_panic_hook() {
+    let mut previous = PREVIOUS_HOOK.lock().unwrap_or_else(|e| e.into_inner());
+    if HOOK_INSTALLED.swap(false, Ordering::Relaxed) {
+        let _ = std::panic::take_hook();
+        if let Some(hook) = previous.take() {
+            std::panic::set_hook(hook);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[test]
+    fn test_outcome_from_panic() {
+        let x = outcome_from_panic(|| 1 + 1);
+        assert_eq!(x.unwrap(), 2);
+
+        // e.g. the hook of a crash reporter, which must survive install and uninstall.
+        static SEEN: AtomicBool = AtomicBool::new(false);
+        let test_thread = std::thread::current().id();
+        std::panic::set_hook(Box::new(move |_| {
+            if std::thread::current().id() == test_thread {
+                SEEN.store(true, Ordering::Relaxed);
+            }
+        }));
+
+        install_panic_hook();
+        let v: Vec<u32> = Vec::new();
+        let line = line!() + 1;
+        let ex = outcome_from_panic(|| v[3]).unwrap_err();
+        uninstall_panic_hook();
+        assert!(!crate::panic_hook::panic_hook_installed());
+        assert!(!SEEN.load(Ordering::Relaxed));
+        let _ = outcome_from_panic(|| panic!("intended"));
+        assert!(SEEN.load(Ordering::Relaxed));
+        let _ = std::panic::take_hook();
+        assert_eq!(ex.get_name(), EXN::PanicException);
+        assert_eq!((ex.get_file(), ex.get_line()), (file!(), line));
+        assert!(ex.get_context().unwrap().contains("index out of bounds"));
+
+        let name = "IntendedException";
+        let x: Outcome<Outcome<()>> = outcome_from_panic(|| throw!(name, ""));
+        assert_eq!(x.unwrap().unwrap_err().get_name(), "IntendedException");
+    }
+}
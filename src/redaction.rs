@@ -0,0 +1,213 @@
+use crate::*;
+use regex::{Captures, Regex};
+use std::sync::{OnceLock, RwLock};
+
+/// What a secret is replaced with.
+pub const REDACTED: &str = "***";
+
+/// Which parts of a text or a JSON value are secrets.
+/// A pattern with a capture group named `secret` only redacts that group,
+/// e.g. `(?i)bearer\s+(?P<secret>\S+)` keeps the word "Bearer";
+/// any other pattern redacts the whole match.
+/// A key redacts the value of a JSON field with that name, ignoring case,
+/// as well as `key=value` and `"key": "value"` in plain text.
+#[derive(Clone, Debug, Default)]
+pub struct RedactionPolicy {
+    patterns: Vec<Regex>,
+    keys: Vec<String>,
+    /// Compiled from `keys`, to find them in plain text.
+    key_patterns: Vec<Regex>,
+}
+
+impl RedactionPolicy {
+    /// A policy that redacts nothing.
+    pub fn new() -> Self {
+        RedactionPolicy::default()
+    }
+
+    /// The policy in effect unless `set_redaction_policy` is called:
+    /// common credential keys and bearer tokens.
+    pub fn standard() -> Self {
+        let mut policy = RedactionPolicy::new();
+        for key in [
+            "password",
+            "passwd",
+            "pwd",
+            "secret",
+            "client_secret",
+            "token",
+            "access_token",
+            "refresh_token",
+            "api_key",
+            "apikey",
+            "private_key",
+            "authorization",
+        ] {
+            policy.add_key(key);
+        }
+        policy
+            .patterns
+            .push(Regex::new(r"(?i)\bbearer\s+(?P<secret>[A-Za-z0-9\-._~+/]+=*)").unwrap());
+        policy
+    }
+
+    /// Throws if `pattern` is not a valid regular expression.
+    pub fn add_pattern(&mut self, pattern: &str) -> Outcome<&mut Self> {
+        let re = Regex::new(pattern).catch(
+            EXN::UncategorizedException,
+            &format!("Invalid redaction pattern `{}`", pattern),
+        )?;
+        self.patterns.push(re);
+        Ok(self)
+    }
+
+    pub fn add_key(&mut self, key: &str) -> &mut Self {
+        self.keys.push(key.to_lowercase());
+        let keys: Vec<String> = self.keys.iter().map(|key| regex::escape(key)).collect();
+        let keys = keys.join("|");
+        self.key_patterns = vec![
+            Regex::new(&format!(
+                r#"(?i)"(?:{})"\s*:\s*"(?P<secret>(?:[^"\\]|\\.)*)""#,
+                keys
+            ))
+            .unwrap(),
+            Regex::new(&format!(r#"(?i)\b(?:{})=(?P<secret>[^&\s"',;]+)"#, keys)).unwrap(),
+        ];
+        self
+    }
+
+    pub fn is_secret_key(&self, key: &str) -> bool {
+        let key = key.to_lowercase();
+        self.keys.contains(&key)
+    }
+
+    pub fn redact_text(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for re in self.key_patterns.iter().chain(self.patterns.iter()) {
+            if re.is_match(&text) {
+                text = re.replace_all(&text, redact_captures).into_owned();
+            }
+        }
+        text
+    }
+
+    /// Replace the values of secret keys, and redact every string in `val`.
+    pub fn redact_json(&self, val: &mut JsonValue) {
+        match val {
+            JsonValue::Object(dict) => {
+                for (key, val) in dict.iter_mut() {
+                    if self.is_secret_key(key) {
+                        *val = JsonValue::String(REDACTED.to_string());
+                    } else {
+                        self.redact_json(val);
+                    }
+                }
+            }
+            JsonValue::Array(vals) => {
+                for val in vals {
+                    self.redact_json(val);
+                }
+            }
+            JsonValue::String(text) => *text = self.redact_text(text),
+            _ => (),
+        }
+    }
+}
+
+fn redact_captures(caps: &Captures) -> String {
+    let whole = caps.get(0).unwrap();
+    match caps.name("secret") {
+        Some(secret) => {
+            let start = secret.start() - whole.start();
+            let end = secret.end() - whole.start();
+            let text = whole.as_str();
+            format!("{}{}{}", &text[..start], REDACTED, &text[end..])
+        }
+        None => REDACTED.to_string(),
+    }
+}
+
+fn policy() -> &'static RwLock<RedactionPolicy> {
+    static POLICY: OnceLock<RwLock<RedactionPolicy>> = OnceLock::new();
+    POLICY.get_or_init(|| RwLock::new(RedactionPolicy::standard()))
+}
+
+/// Set the policy applied when an `Exception` is rendered,
+/// and by `obj_to_json_pretty_redacted`. `RedactionPolicy::new()` turns redaction off.
+pub fn set_redaction_policy(new: RedactionPolicy) {
+    *policy().write().unwrap_or_else(|e| e.into_inner()) = new;
+}
+
+pub fn redaction_policy() -> RedactionPolicy {
+    policy().read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Apply the global policy to `text`.
+pub fn redact_text(text: &str) -> String {
+    policy()
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .redact_text(text)
+}
+
+/// Apply the global policy to `val`.
+pub fn redact_json(val: &mut JsonValue) {
+    policy()
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .redact_json(val)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_text() -> Outcome<()> {
+        let policy = RedactionPolicy::standard();
+        assert_eq!(
+            policy.redact_text("GET https://x.io/a?user=bob&token=abc123&page=2"),
+            "GET https://x.io/a?user=bob&token=***&page=2"
+        );
+        assert_eq!(
+            policy.redact_text(r#"body {"user": "bob", "Password" : "p\"w"}"#),
+            r#"body {"user": "bob", "Password" : "***"}"#
+        );
+        assert_eq!(
+            policy.redact_text("Authorization: Bearer eyJhbGci.eyJzdWIi"),
+            "Authorization: Bearer ***"
+        );
+        assert_eq!(policy.redact_text("tokenizer=bpe"), "tokenizer=bpe");
+
+        let mut policy = RedactionPolicy::new();
+        assert_eq!(policy.redact_text("token=abc"), "token=abc");
+        policy
+            .add_pattern(r"\d{4}-\d{4}-\d{4}-\d{4}")?
+            .add_key("pin");
+        assert_eq!(
+            policy.redact_text("card 1234-5678-9012-3456, pin=0000"),
+            "card ***, pin=***"
+        );
+        assert!(policy.add_pattern("(").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_redact_json() {
+        let policy = RedactionPolicy::standard();
+        let mut val = serde_json::json!({
+            "user": "bob",
+            "TOKEN": {"nested": 1},
+            "items": [{"api_key": "k"}, "see ?token=abc"],
+        });
+        policy.redact_json(&mut val);
+        assert_eq!(
+            val,
+            serde_json::json!({
+                "user": "bob",
+                "TOKEN": "***",
+                "items": [{"api_key": "***"}, "see ?token=***"],
+            })
+        );
+    }
+}
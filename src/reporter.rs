@@ -0,0 +1,347 @@
+use crate::*;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "http")]
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+#[cfg(feature = "http")]
+use std::time::Duration;
+
+/// A sink for the exceptions that reach the top of the program:
+/// those returned to `run_main`, those caught by the hook of `install_panic_hook`,
+/// and those passed to `report`. See `register_reporter`.
+///
+/// Any `Fn(&Exception) -> Outcome<()>` is a `Reporter`.
+pub trait Reporter: Send + Sync {
+    fn report(&self, ex: &Exception) -> Outcome<()>;
+}
+
+impl<F> Reporter for F
+where
+    F: Fn(&Exception) -> Outcome<()> + Send + Sync,
+{
+    fn report(&self, ex: &Exception) -> Outcome<()> {
+        self(ex)
+    }
+}
+
+/// Identifies a sink added by `register_reporter`, see `unregister_reporter`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ReporterId(u64);
+
+type Registered = Vec<(ReporterId, Arc<dyn Reporter>)>;
+
+fn reporters() -> &'static RwLock<Registered> {
+    static REPORTERS: OnceLock<RwLock<Registered>> = OnceLock::new();
+    REPORTERS.get_or_init(|| RwLock::new(Vec::new()))
+}
+
+/// Add a sink that receives every reported exception, after those registered before it.
+pub fn register_reporter(reporter: impl Reporter + 'static) -> ReporterId {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+    let id = ReporterId(NEXT_ID.fetch_add(1, Ordering::Relaxed));
+    let mut reporters = reporters().write().unwrap_or_else(|e| e.into_inner());
+    reporters.push((id, Arc::new(reporter)));
+    id
+}
+
+/// Remove the sink registered as `id`. Returns whether it was still registered.
+pub fn unregister_reporter(id: ReporterId) -> bool {
+    let mut reporters = reporters().write().unwrap_or_else(|e| e.into_inner());
+    let len = reporters.len();
+    reporters.retain(|(other, _)| *other != id);
+    reporters.len() != len
+}
+
+/// Remove every registered sink.
+pub fn clear_reporters() {
+    reporters()
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .clear();
+}
+
+/// Hand `ex` to every registered sink.
+/// A sink that fails is skipped, and its exception is printed to stderr.
+/// The sinks run without holding the registry lock, so a slow sink does not block `register_reporter`,
+/// and a sink may itself register, unregister or report.
+pub fn report(ex: &Exception) {
+    let reporters: Vec<Arc<dyn Reporter>> = reporters()
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .map(|(_, reporter)| reporter.clone())
+        .collect();
+    for reporter in reporters {
+        if let Err(err) = reporter.report(ex) {
+            eprintln!(
+                "Failed to report exception \"{}\": {:#}",
+                ex.get_name(),
+                err
+            );
+        }
+    }
+}
+
+/// Appends each exception to a file as a line of `ExceptionFormat::Json`.
+pub struct JsonLinesReporter {
+    path: String,
+    file: Mutex<File>,
+}
+
+impl JsonLinesReporter {
+    /// Opens `path` for appending, creating it if necessary.
+    pub fn open(path: &str) -> Outcome<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .catch(
+                EXN::IOException,
+                &format!("Cannot open \"{}\" for appending", path),
+            )?;
+        Ok(JsonLinesReporter {
+            path: path.to_string(),
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl Reporter for JsonLinesReporter {
+    fn report(&self, ex: &Exception) -> Outcome<()> {
+        let line = ex.render(ExceptionFormat::Json) + "\n";
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        file.write_all(line.as_bytes()).catch(
+            EXN::IOException,
+            &format!("Cannot append to \"{}\"", self.path),
+        )?;
+        Ok(())
+    }
+}
+
+/// Prints each exception to stderr in `format`.
+/// Note that `run_main` and the panic hook print to stderr anyway.
+pub struct StderrReporter {
+    format: ExceptionFormat,
+}
+
+impl StderrReporter {
+    pub fn new(format: ExceptionFormat) -> Self {
+        StderrReporter { format }
+    }
+}
+
+impl Reporter for StderrReporter {
+    fn report(&self, ex: &Exception) -> Outcome<()> {
+        eprintln!("{}", ex.render(self.format).trim_end());
+        Ok(())
+    }
+}
+
+/// POSTs each exception as `ExceptionFormat::Json` to a collector endpoint.
+/// The request is sent from a background thread owned by the reporter, with the blocking client of `reqwest`,
+/// since that client panics on a thread of an async runtime, where `report` may well be called.
+/// `report` waits for the response.
+#[cfg(feature = "http")]
+pub struct HttpReporter {
+    url: String,
+    timeout: Duration,
+    worker: OnceLock<Sender<HttpJob>>,
+}
+
+#[cfg(feature = "http")]
+struct HttpJob {
+    url: String,
+    timeout: Duration,
+    body: String,
+    reply: Sender<Outcome<()>>,
+}
+
+#[cfg(feature = "http")]
+impl HttpJob {
+    fn post(&self) -> Outcome<()> {
+        let ctx = format!("Cannot POST the exception to \"{}\"", self.url);
+        let client = reqwest::blocking::Client::builder()
+            .timeout(self.timeout)
+            .build()
+            .catch(EXN::HttpPostException, &ctx)?;
+        client
+            .post(&self.url)
+            .header("Content-Type", "application/json")
+            .body(self.body.clone())
+            .send()
+            .and_then(|resp| resp.error_for_status())
+            .catch(EXN::HttpPostException, &ctx)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "http")]
+impl HttpReporter {
+    /// The timeout defaults to 5 seconds.
+    pub fn new(url: &str) -> Self {
+        HttpReporter {
+            url: url.to_string(),
+            timeout: Duration::from_secs(5),
+            worker: OnceLock::new(),
+        }
+    }
+
+    pub fn set_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Started on the first report, and stopped when the reporter is dropped.
+    fn worker(&self) -> &Sender<HttpJob> {
+        self.worker.get_or_init(|| {
+            let (jobs, queue) = mpsc::channel::<HttpJob>();
+            // If the thread cannot be spawned, `queue` is dropped and every report fails.
+            let _ = std::thread::Builder::new()
+                .name("http-reporter".to_string())
+                .spawn(move || {
+                    for job in queue {
+                        let _ = job.reply.send(job.post());
+                    }
+                });
+            jobs
+        })
+    }
+}
+
+#[cfg(feature = "http")]
+impl Reporter for HttpReporter {
+    fn report(&self, ex: &Exception) -> Outcome<()> {
+        let ctx = format!("Cannot POST the exception to \"{}\"", self.url);
+        let (reply, response) = mpsc::channel();
+        let job = HttpJob {
+            url: self.url.clone(),
+            timeout: self.timeout,
+            body: ex.render(ExceptionFormat::Json),
+            reply,
+        };
+        self.worker()
+            .send(job)
+            .catch(EXN::HttpPostException, &ctx)?;
+        response.recv().catch(EXN::HttpPostException, &ctx)?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "http")]
+    use std::io::{BufRead, BufReader, Read};
+    #[cfg(feature = "http")]
+    use std::net::TcpListener;
+    use std::sync::Arc;
+
+    fn fail() -> Outcome<()> {
+        let name = "ReporterTestException";
+        throw!(name, "token=abc123");
+    }
+
+    #[test]
+    fn test_registry() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        // other tests may report concurrently, so both sinks only handle this test's exception.
+        let failing = register_reporter(|ex: &Exception| {
+            if ex.get_name() == "ReporterTestException" {
+                throw!(EXN::IOException, "intended");
+            }
+            Ok(())
+        });
+        let collecting = register_reporter(move |ex: &Exception| {
+            if ex.get_name() == "ReporterTestException" {
+                sink.lock().unwrap().push(ex.to_string());
+            }
+            Ok(())
+        });
+        report(&fail().unwrap_err());
+        let code = run_main(fail);
+        assert_eq!(code, std::process::ExitCode::from(DEFAULT_EXIT_CODE));
+        assert_eq!(seen.lock().unwrap().len(), 2);
+
+        assert!(unregister_reporter(failing));
+        assert!(unregister_reporter(collecting));
+        assert!(!unregister_reporter(collecting));
+        report(&fail().unwrap_err());
+        assert_eq!(seen.lock().unwrap().len(), 2);
+
+        // a sink may unregister itself, since `report` does not hold the registry lock.
+        let own_id = Arc::new(OnceLock::new());
+        let id = own_id.clone();
+        let reentrant = register_reporter(move |ex: &Exception| {
+            if let (Some(id), "ReporterTestException") = (id.get(), ex.get_name()) {
+                unregister_reporter(*id);
+            }
+            Ok(())
+        });
+        own_id.set(reentrant).unwrap();
+        report(&fail().unwrap_err());
+        assert!(!unregister_reporter(reentrant));
+    }
+
+    #[test]
+    fn test_json_lines() -> Outcome<()> {
+        let path = std::env::temp_dir().join(format!("xuanmi-report-{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap();
+        let reporter = JsonLinesReporter::open(path)?;
+        reporter.report(&fail().unwrap_err())?;
+        reporter.report(&fail().unwrap_err())?;
+        let text = read_str_from_file(path)?;
+        std::fs::remove_file(path).catch_()?;
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let ex: Exception = json_to_obj(lines[1])?;
+        assert_eq!(ex.get_name(), "ReporterTestException");
+        assert_eq!(ex.get_context(), Some("token=***"));
+        Ok(())
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_http() -> Outcome<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").catch_()?;
+        let url = format!("http://{}/exceptions", listener.local_addr().catch_()?);
+        let stub = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut head = String::new();
+            let mut len = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(val) = line.to_lowercase().strip_prefix("content-length:") {
+                    len = val.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+                head += &line;
+            }
+            let mut body = vec![0; len];
+            reader.read_exact(&mut body).unwrap();
+            let mut stream = reader.into_inner();
+            stream
+                .write_all(b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+            (head, String::from_utf8(body).unwrap())
+        });
+
+        let mut reporter = HttpReporter::new(&url);
+        reporter.set_timeout(Duration::from_secs(10));
+        reporter.report(&fail().unwrap_err())?;
+        let (head, body) = stub.join().unwrap();
+        assert!(head.starts_with("POST /exceptions HTTP/1.1"));
+        let ex: Exception = json_to_obj(&body)?;
+        assert_eq!(ex.get_name(), "ReporterTestException");
+
+        // nothing listens on the port any more.
+        let ex = reporter.report(&fail().unwrap_err()).unwrap_err();
+        assert_eq!(ex.get_name(), EXN::HttpPostException);
+        Ok(())
+    }
+}
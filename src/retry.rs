@@ -0,0 +1,280 @@
+use crate::*;
+use rand::Rng;
+use std::future::Future;
+use std::panic::Location;
+use std::time::{Duration, Instant};
+
+pub enum Backoff {
+    /// Wait the same duration before every retry.
+    Fixed(Duration),
+    /// Wait `initial * factor^(n-1)` before the n-th retry, but no longer than `max`.
+    Exponential {
+        initial: Duration,
+        factor: f64,
+        max: Duration,
+    },
+}
+
+/// When and how long to wait before retrying a failed call. See `retry` and `retry_async`.
+/// By default, at most 3 attempts are made, and every exception is retryable.
+pub struct RetryPolicy {
+    backoff: Backoff,
+    jitter: bool,
+    max_attempts: Option<u32>,
+    max_elapsed: Option<Duration>,
+    retryable: Box<dyn Fn(&Exception) -> bool + Send + Sync>,
+}
+
+impl RetryPolicy {
+    pub fn new(backoff: Backoff) -> Self {
+        RetryPolicy {
+            backoff,
+            jitter: false,
+            max_attempts: Some(3),
+            max_elapsed: None,
+            retryable: Box::new(|_| true),
+        }
+    }
+
+    pub fn fixed(delay: Duration) -> Self {
+        RetryPolicy::new(Backoff::Fixed(delay))
+    }
+
+    /// Doubles the delay after every retry, up to `max`.
+    pub fn exponential(initial: Duration, max: Duration) -> Self {
+        RetryPolicy::new(Backoff::Exponential {
+            initial,
+            factor: 2.0,
+            max,
+        })
+    }
+
+    /// Wait a uniformly random duration between zero and the computed delay,
+    /// so that clients failing together do not retry together.
+    #[inline]
+    pub fn set_jitter(&mut self, jitter: bool) -> &mut Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// `None` means no limit on the number of attempts.
+    #[inline]
+    pub fn set_max_attempts(&mut self, max_attempts: Option<u32>) -> &mut Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Give up instead of sleeping past `max_elapsed` since the first attempt.
+    #[inline]
+    pub fn set_max_elapsed(&mut self, max_elapsed: Option<Duration>) -> &mut Self {
+        self.max_elapsed = max_elapsed;
+        self
+    }
+
+    /// Retry only if `pred` holds for the exception of the failed attempt.
+    /// Otherwise that exception is returned as-is.
+    pub fn set_retryable<F>(&mut self, pred: F) -> &mut Self
+    where
+        F: Fn(&Exception) -> bool + Send + Sync + 'static,
+    {
+        self.retryable = Box::new(pred);
+        self
+    }
+
+    /// Retry only exceptions that `is_a` one of `categories`.
+    pub fn set_retryable_names(&mut self, categories: &[&str]) -> &mut Self {
+        let categories: Vec<String> = categories.iter().map(|c| c.to_string()).collect();
+        self.set_retryable(move |ex| categories.iter().any(|c| ex.is_a(c)))
+    }
+
+    /// The delay before the `retry`-th retry, counting from 1, without jitter.
+    pub fn delay(&self, retry: u32) -> Duration {
+        match self.backoff {
+            Backoff::Fixed(delay) => delay,
+            Backoff::Exponential {
+                initial,
+                factor,
+                max,
+            } => {
+                let exp = retry.saturating_sub(1).min(i32::MAX as u32) as i32;
+                let secs = initial.as_secs_f64() * factor.powi(exp);
+                Duration::try_from_secs_f64(secs).unwrap_or(max).min(max)
+            }
+        }
+    }
+}
+
+/// The bookkeeping shared by `retry` and `retry_async`.
+struct Attempts<'a> {
+    policy: &'a RetryPolicy,
+    loc: &'static Location<'static>,
+    start: Instant,
+    errs: MultiException,
+}
+
+impl<'a> Attempts<'a> {
+    fn new(policy: &'a RetryPolicy, loc: &'static Location<'static>) -> Self {
+        Attempts {
+            policy,
+            loc,
+            start: Instant::now(),
+            errs: MultiException::new(),
+        }
+    }
+
+    /// How long to sleep before the next attempt, or the exception to give up with.
+    fn on_error(&mut self, ex: Box<Exception>) -> Result<Duration, Box<Exception>> {
+        if !(self.policy.retryable)(&ex) {
+            return Err(ex);
+        }
+        self.errs.push(ex);
+        let attempts = self.errs.len() as u32;
+        let mut delay = self.policy.delay(attempts);
+        if self.policy.jitter && !delay.is_zero() {
+            delay = rand::thread_rng().gen_range(Duration::ZERO..=delay);
+        }
+        let out_of_attempts = match self.policy.max_attempts {
+            Some(max_attempts) => attempts >= max_attempts,
+            None => false,
+        };
+        let out_of_time = match self.policy.max_elapsed {
+            Some(max_elapsed) => self.start.elapsed() + delay > max_elapsed,
+            None => false,
+        };
+        if !(out_of_attempts || out_of_time) {
+            return Ok(delay);
+        }
+        let errs = std::mem::take(&mut self.errs);
+        let mut ex = Exception::new();
+        ex.set_name(EXN::RetryExhaustedException)
+            .set_file(self.loc.file())
+            .set_line(self.loc.line())
+            .set_column(self.loc.column())
+            .set_context(&format!("Gave up after {} attempts", attempts))
+            .set_field("attempts", &attempts)
+            .set_caused_by(errs);
+        ex.emit_event();
+        Err(ex)
+    }
+}
+
+/// Call `f` until it succeeds, sleeping between attempts as `policy` says.
+/// Once `policy` gives up, a `RetryExhaustedException` is thrown,
+/// with the exception of every attempt in a `MultiException` beneath it.
+#[track_caller]
+pub fn retry<T, F>(policy: &RetryPolicy, mut f: F) -> Outcome<T>
+where
+    F: FnMut() -> Outcome<T>,
+{
+    let mut attempts = Attempts::new(policy, Location::caller());
+    loop {
+        match f() {
+            Ok(v) => return Ok(v),
+            Err(ex) => std::thread::sleep(attempts.on_error(ex)?),
+        }
+    }
+}
+
+/// The async counterpart of `retry`.
+/// This crate does not depend on an async runtime,
+/// so the caller provides `sleep`, e.g. `tokio::time::sleep`.
+#[track_caller]
+pub fn retry_async<'a, T, F, Fut, S, SleepFut>(
+    policy: &'a RetryPolicy,
+    sleep: S,
+    mut f: F,
+) -> impl Future<Output = Outcome<T>> + 'a
+where
+    F: FnMut() -> Fut + 'a,
+    Fut: Future<Output = Outcome<T>> + 'a,
+    S: Fn(Duration) -> SleepFut + 'a,
+    SleepFut: Future<Output = ()> + 'a,
+{
+    let loc = Location::caller();
+    async move {
+        let mut attempts = Attempts::new(policy, loc);
+        loop {
+            match f().await {
+                Ok(v) => return Ok(v),
+                Err(ex) => sleep(attempts.on_error(ex)?).await,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::time::Duration;
+
+    fn flaky(calls: &mut u32, succeed_at: u32) -> Outcome<u32> {
+        *calls += 1;
+        if *calls < succeed_at {
+            throw!(EXN::HttpPostException, &format!("attempt {}", calls));
+        }
+        Ok(*calls)
+    }
+
+    #[test]
+    fn test_retry() {
+        let mut policy = RetryPolicy::fixed(Duration::ZERO);
+        policy.set_max_attempts(Some(5));
+        let mut calls = 0;
+        assert_eq!(retry(&policy, || flaky(&mut calls, 3)).unwrap(), 3);
+
+        let mut calls = 0;
+        let ex = retry(&policy, || flaky(&mut calls, 10)).unwrap_err();
+        assert_eq!(calls, 5);
+        assert_eq!(ex.get_name(), EXN::RetryExhaustedException);
+        assert_eq!(ex.get_field("attempts"), Some(&field_value(&5)));
+        let multi = ex.root_cause().downcast_ref::<MultiException>().unwrap();
+        assert_eq!(multi.get_children()[4].get_context(), Some("attempt 5"));
+
+        policy.set_retryable_names(&[EXN::ArithmeticException]);
+        let mut calls = 0;
+        let ex = retry(&policy, || flaky(&mut calls, 10)).unwrap_err();
+        assert_eq!(calls, 1);
+        assert_eq!(ex.get_name(), EXN::HttpPostException);
+    }
+
+    #[test]
+    fn test_retry_max_elapsed() {
+        let mut policy = RetryPolicy::fixed(Duration::from_millis(20));
+        policy
+            .set_max_attempts(None)
+            .set_max_elapsed(Some(Duration::from_millis(50)));
+        let mut calls = 0;
+        let ex = retry(&policy, || flaky(&mut calls, 100)).unwrap_err();
+        // normally 3, fewer if the sleeps overshoot.
+        assert!((1..=3).contains(&calls));
+        assert_eq!(ex.get_field("attempts"), Some(&field_value(&calls)));
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::exponential(Duration::from_millis(100), Duration::from_secs(1));
+        let delays: Vec<u128> = (1..=6).map(|n| policy.delay(n).as_millis()).collect();
+        assert_eq!(delays, [100, 200, 400, 800, 1000, 1000]);
+        assert_eq!(policy.delay(u32::MAX), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_retry_async() {
+        let mut policy =
+            RetryPolicy::exponential(Duration::from_millis(1), Duration::from_millis(5));
+        policy.set_jitter(true);
+        let mut calls = 0;
+        let sleep = |d| async move { std::thread::sleep(d) };
+        let fut = retry_async(&policy, sleep, || {
+            calls += 1;
+            let n = calls;
+            async move {
+                if n < 2 {
+                    throw!(EXN::IOException, "");
+                }
+                Ok(n)
+            }
+        });
+        assert_eq!(futures::executor::block_on(fut).unwrap(), 2);
+    }
+}
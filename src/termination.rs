@@ -0,0 +1,104 @@
+use crate::*;
+use std::panic::AssertUnwindSafe;
+use std::process::ExitCode;
+use std::sync::{OnceLock, RwLock};
+
+/// Exit code for exceptions that match no entry of the table.
+pub const DEFAULT_EXIT_CODE: u8 = 1;
+
+fn exit_code_table() -> &'static RwLock<Vec<(String, u8)>> {
+    static TABLE: OnceLock<RwLock<Vec<(String, u8)>>> = OnceLock::new();
+    // 101 is what Rust exits with on an uncaught panic.
+    TABLE.get_or_init(|| RwLock::new(vec![(EXN::PanicException.to_string(), 101)]))
+}
+
+/// Let `run_main` exit with `code` for exceptions that `is_a(category)`.
+/// Later entries take precedence, so that a sub-category can be given its own code.
+pub fn set_exit_code(category: &str, code: u8) {
+    let mut table = exit_code_table().write().unwrap_or_else(|e| e.into_inner());
+    table.retain(|(c, _)| c != category);
+    table.push((category.to_string(), code));
+}
+
+pub fn exit_code_of(ex: &Exception) -> u8 {
+    let table = exit_code_table().read().unwrap_or_else(|e| e.into_inner());
+    match table.iter().rev().find(|(category, _)| ex.is_a(category)) {
+        Some((_, code)) => *code,
+        None => DEFAULT_EXIT_CODE,
+    }
+}
+
+/// Run the body of `main`. If it throws or panics,
+/// the exception is logged through `tracing`, printed to stderr in `exception_format()`,
+/// passed to `report`, and turned into the exit code given by `set_exit_code`.
+/// A panic is left to the hook of `install_panic_hook` if installed, so that it is reported once.
+/// Otherwise it is not printed again, since the default hook has already printed it.
+///
+/// The process is not exited here. Return the `ExitCode` from `main`,
+/// so that the guard of `init_tracer!` is dropped, which flushes the log file:
+/// ```ignore
+/// fn main() -> std::process::ExitCode {
+///     init_tracer!("/var/log/app", "app.log", "info");
+///     run_main(|| { /* ... */ Ok(()) })
+/// }
+/// ```
+pub fn run_main<F>(f: F) -> ExitCode
+where
+    F: FnOnce() -> Outcome<()>,
+{
+    // The process ends right after, so no broken invariant can be observed.
+    match outcome_from_panic(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => ExitCode::SUCCESS,
+        Ok(Err(ex)) => {
+            #[cfg(feature = "tracing")]
+            tracing::error!("{:#}", ex);
+            eprintln!("{}", ex.to_string().trim_end());
+            report(&ex);
+            ExitCode::from(exit_code_of(&ex))
+        }
+        Err(ex) => {
+            // Either hook has already printed the panic to stderr.
+            if !crate::panic_hook::panic_hook_installed() {
+                #[cfg(feature = "tracing")]
+                tracing::error!("{:#}", ex);
+                report(&ex);
+            }
+            ExitCode::from(exit_code_of(&ex))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn test_exit_code() {
+        fn fail(name: &str) -> Box<Exception> {
+            let res: Outcome<()> = (|| throw!(name, ""))();
+            res.unwrap_err()
+        }
+        assert_eq!(exit_code_of(&fail(EXN::PanicException)), 101);
+        assert_eq!(
+            exit_code_of(&fail(EXN::HttpPostException)),
+            DEFAULT_EXIT_CODE
+        );
+
+        set_exit_code("TestExitIOException", 74);
+        set_exit_code("TestExitHttpException", 69);
+        EXN::register_exception_name("TestExitIOException", None).unwrap();
+        EXN::register_exception_name("TestExitHttpException", Some("TestExitIOException")).unwrap();
+        EXN::register_exception_name("TestExitFileException", Some("TestExitIOException")).unwrap();
+        assert_eq!(exit_code_of(&fail("TestExitHttpException")), 69);
+        assert_eq!(exit_code_of(&fail("TestExitFileException")), 74);
+    }
+
+    #[test]
+    fn test_run_main() {
+        assert_eq!(run_main(|| Ok(())), std::process::ExitCode::SUCCESS);
+        let code = run_main(|| throw!(EXN::IOException, "intended"));
+        assert_eq!(code, std::process::ExitCode::from(DEFAULT_EXIT_CODE));
+        let code = run_main(|| panic!("intended"));
+        assert_eq!(code, std::process::ExitCode::from(101));
+    }
+}